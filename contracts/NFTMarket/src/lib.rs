@@ -1,18 +1,28 @@
 #![no_std]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, log, symbol_short,
-    Address, Bytes, Env, Symbol, String, Vec,
+    contract, contracterror, contractevent, contractimpl, contracttype, log, symbol_short,
+    xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal, Symbol, String, TryFromVal, Val, Vec,
 };
 
 
 // ========== Token keys ==========
 const ADMIN:    Symbol = symbol_short!("ADM");   // Địa chỉ admin (cần auth cho admin-only)
+const PEND_ADM: Symbol = symbol_short!("PADM");  // Admin được đề cử, chờ accept_admin
+const ROLE:     Symbol = symbol_short!("ROLE");  // (ROLE, Role, Address) → true
+const PAUSE:    Symbol = symbol_short!("PAUSE"); // (PAUSE, Feature) → true khi đang tạm dừng
+const COUNCIL:  Symbol = symbol_short!("CNCL");  // Council M-of-N (không có → chỉ ADMIN)
+const PROP:     Symbol = symbol_short!("PROP");  // (PROP, id:u32) → Proposal
+const PROP_NID: Symbol = symbol_short!("PNID");  // ID proposal tiếp theo (u32)
 const NAME:     Symbol = symbol_short!("NAME");  // Tên token FT
 const SYMBOL_:  Symbol = symbol_short!("SYMB");  // Ký hiệu token FT
 const DECIMALS: Symbol = symbol_short!("DEC");   // Số lẻ thập phân token FT (<=18)
 const SUPPLY:   Symbol = symbol_short!("SUP");   // Tổng cung FT
+const MAX_SUP:  Symbol = symbol_short!("MSUP");  // Trần tổng cung FT (raw, tuỳ chọn, đặt một lần khi init)
 const INITED:   Symbol = symbol_short!("INI");   // Đã init hay chưa
+const VERSION:  Symbol = symbol_short!("VER");   // Phiên bản schema storage (không có → 1)
 const BAL:      Symbol = symbol_short!("BAL");   // (BAL, Address) → i128 số dư FT
+const MAX_DECIMALS: u32 = 18;
+const ALLOW:    Symbol = symbol_short!("ALW");   // (ALW, from, spender) → AllowanceValue (temporary storage)
 
 // ========== NFT keys ==========
 const NFT_SUP:  Symbol = symbol_short!("NSUP");  // Tổng số NFT đã mint (i128)
@@ -21,464 +31,3087 @@ const NEXT_ID:  Symbol = symbol_short!("NID");   // ID NFT tiếp theo (i128)
 const NFT:      Symbol = symbol_short!("NFT");   // (NFT, id:u32) → NftData
 const OWN:      Symbol = symbol_short!("OWN");   // (OWN, owner:Address) → Vec<u32> (danh sách id)
 const PAL:      Symbol = symbol_short!("PAL");   // Bảng màu Vec<u32> (32 màu 0xRRGGBB)
+const NFT_APR:  Symbol = symbol_short!("NAPR");  // (NAPR, id) → NftApproval (temporary storage, xoá khi chuyển)
+const NFT_OPR:  Symbol = symbol_short!("NOPR");  // (NOPR, owner, operator) → true: operator quản lý mọi NFT của owner
+
+const PMINT:    Symbol = symbol_short!("PMINT"); // PublicMintConfig (không có → tắt mint công khai)
+const PM_CNT:   Symbol = symbol_short!("PMC");   // (PMC, creator) → u32 số NFT đã mint_public
+const CMT:      Symbol = symbol_short!("CMT");   // (CMT, creator, hash) → MintCommitment (cọc giữ ở số dư của contract)
+const CMT_REQ:  Symbol = symbol_short!("CREQ");  // true (mặc định) → mint công khai / presale bắt buộc qua commit-reveal
+const REVEAL_MIN_DELAY: u32 = 1;                 // reveal sớm nhất sau commit (ledger)
+const REVEAL_WINDOW:    u32 = 720;               // reveal muộn nhất sau commit (~1 giờ)
+const DSGN:     Symbol = symbol_short!("DSGN");  // (DSGN, id) → DesignSubmission đang chờ duyệt
+const DSGN_UNQ: Symbol = symbol_short!("DUNQ");  // (DUNQ, pixels) → id submission giữ chỗ pattern
+const DSGN_IDS: Symbol = symbol_short!("DIDS");  // Vec<u32> id submission đang chờ (persistent)
+const DSGN_NID: Symbol = symbol_short!("DNID");  // ID submission tiếp theo (u32)
+const DSGN_FEE: Symbol = symbol_short!("DFEE");  // Cọc nộp bài (i128 raw, không có → 0)
+const AL_CFG:   Symbol = symbol_short!("ALCFG"); // AllowlistConfig (không có → đóng presale)
+const AL_CNT:   Symbol = symbol_short!("ALC");   // (ALC, root, to) → u32 đã mint theo allowlist `root`
+const MAX_PROOF_LEN: u32 = 32;
+
+const ROY:      Symbol = symbol_short!("ROY");   // (ROY, id) → RoyaltyInfo (ghi khi mint)
+const ROY_BPS:  Symbol = symbol_short!("ROYB");  // Royalty mặc định (bps) cho NFT mint mới (không có → 0)
+const MAX_ROYALTY_BPS: u32 = 2_500;
 
 // Uniqueness & Search index
 const UNIQ:     Symbol = symbol_short!("UNIQ");  // (UNIQ, pixels:Bytes) → id (đảm bảo độc nhất theo pixel)
 const IDX:      Symbol = symbol_short!("IDX");   // (IDX, pos:u32, col:u32) → Vec<u32> (IDs có màu col ở vị trí pos)
+const IDX_MASK: Symbol = symbol_short!("IDXM");  // (IDXM, id) → u128 bitmask các vị trí đã index (bit i = pos i)
+const IDX_OLD:  Symbol = symbol_short!("IDXO");  // u32: NFT có id < giá trị này được index trước v4, không có IDX_MASK
 
 // ========== Marketplace keys ==========
 const LST:      Symbol = symbol_short!("LST");   // (LST, id) → Listing (thông tin listing)
-const LIDS:     Symbol = symbol_short!("LIDS");  // Vec<u32> danh sách id đang niêm yết
+const LIDS:     Symbol = symbol_short!("LIDS");  // Vec<u32> danh sách id đang niêm yết (persistent từ schema v2)
 const LSTFEE:   Symbol = symbol_short!("LFEE");  // Phí listing (i128, tính bằng “raw” theo decimals FT)
-
-// ===== Transfer fee: cố định 1 “raw” (0.001 token vì decimals=3) =====
+const LST_UPD:  Symbol = symbol_short!("LUPD");  // (LUPD, id) → ledger đặt giá gần nhất của listing
+const LST_PRC:  Symbol = symbol_short!("LPRC");  // u32 vị trí trong LIDS mà market_prune_expired quét tiếp
+const LST_NON:  Symbol = symbol_short!("LNON");  // (LNON, id) → u32 nonce, tăng mỗi lần niêm yết / đổi giá
+const LST_CD:   Symbol = symbol_short!("LCD");   // Cooldown tăng giá listing (ledger, không có → 0)
+const SALE_FEE: Symbol = symbol_short!("SFEE");  // Phí giao dịch (bps) trích từ tiền bán, không có → 0
+const MAX_SALE_FEE_BPS: u32 = 2_500;
+
+// ========== Timelock keys ==========
+const TL_DELAY: Symbol = symbol_short!("TLD");   // Độ trễ tối thiểu (ledger, u32); 0/không có → tắt timelock
+const TL_Q:     Symbol = symbol_short!("TLQ");   // (TLQ, id:u32) → ScheduledChange
+const TL_IDS:   Symbol = symbol_short!("TLIDS"); // Vec<u32> id các thay đổi đang chờ (persistent)
+const TL_NID:   Symbol = symbol_short!("TLNID"); // ID thay đổi tiếp theo (u32)
+const TL_MAX_DELAY: u32 = 30 * DAY_IN_LEDGERS;
+
+// ===== Transfer fee: mặc định cố định 1 “raw” (0.001 token khi decimals=3) =====
 const TRANSFER_FEE_RAW: i128 = 1;
+const TFEE:     Symbol = symbol_short!("TFEE");  // TransferFeeConfig (không có → Flat TRANSFER_FEE_RAW)
+const TREASURY: Symbol = symbol_short!("TRSY");  // Địa chỉ nhận phí (không có → ADMIN)
+const BPS_DENOM: i128 = 10_000;
+const FEE_EXEMPT: Symbol = symbol_short!("FEX");  // (FEX, Address) → true: miễn phí transfer, listing & phí bán
 
 // ✅ Để FALSE: không đánh index toàn bộ khi mint (tránh vượt footprint simulate)
 const ENABLE_POS_INDEX: bool = false;
 
+// ========== TTL (ledger) ==========
+const TTL_CFG:  Symbol = symbol_short!("TTL");   // TtlConfig (không có → mặc định bên dưới)
+const DAY_IN_LEDGERS: u32 = 17_280;              // ~5s / ledger
+const INSTANCE_TTL_THRESHOLD:   u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_TTL_EXTEND_TO:   u32 = 30 * DAY_IN_LEDGERS;
+const PERSISTENT_TTL_THRESHOLD: u32 = 30 * DAY_IN_LEDGERS;
+const PERSISTENT_TTL_EXTEND_TO: u32 = 120 * DAY_IN_LEDGERS;
+
+// ========== Schema storage ==========
+// v1: bản đầu tiên (LIDS ở instance storage)
+// v2: LIDS chuyển sang persistent storage
+// v3: Listing thêm expires_ledger
+// v4: IDX_MASK ghi vị trí đã index; NFT cũ (id < IDX_OLD) burn phải quét đủ 81 vị trí
+const SCHEMA_VERSION: u32 = 4;
+
 // ========== Cấu hình kích thước NFT ==========
 const NFT_SIZE:   u32 = 9;
 const NFT_PIXELS: u32 = NFT_SIZE * NFT_SIZE; // 81
 
 
-#[contracttype]
-#[derive(Clone)]
-pub struct NftData {
-    pub owner: Address,
-    pub pixels: Bytes,
+// ========== Mã lỗi (ổn định, không đổi số khi thêm lỗi mới) ==========
+// Spec XDR chỉ chứa tối đa 50 case → không export enum vào contract spec;
+// client dùng bảng mã CONTRACT_ERRORS trong nft-dapp/src/stellar.ts.
+#[contracterror(export = false)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+#[repr(u32)]
+pub enum Error {
+    // Init / cấu hình
+    AlreadyInit = 1,
+    NotInited = 2,
+    BadDecimals = 3,
+    SupplyOverflow = 4,
+    BadFeeConfig = 5,
+    BadSupply = 6,
+    AllocationExceedsSupply = 7,
+    MaxSupplyExceeded = 8,
+    MigrationRequired = 9,
+
+    // Token FT
+    BadAmount = 10,
+    BadAmountAt = 11,
+    SelfTransfer = 12,
+    SelfTransferAt = 13,
+    EmptyBatch = 14,
+    LengthMismatch = 15,
+    InsufficientBalance = 16,
+    InsufficientBalanceWithFee = 17,
+    InsufficientAllowance = 18,
+    BadExpirationLedger = 19,
+    BalOverflow = 20,
+    FeeAddOverflow = 21,
+    FeeMulOverflow = 22,
+    AmountSumOverflow = 23,
+    TotalDeductOverflow = 24,
+
+    // NFT
+    PixelsLen81 = 30,
+    PixelOutOfRange = 31,
+    DuplicateValue = 32,
+    NftMaxSupReached = 33,
+    NftIdExhausted = 34,
+    NftNotFound = 35,
+    NotOwner = 36,
+    OwnershipCorrupted = 37,
+    BadRange = 38,
+    PosRange0To80 = 39,
+    ColorRange0To31 = 40,
+    Palette32Required = 41,
+    NoPalette = 42,
+    NotApproved = 43,
+    NotCreator = 44,
+    BadRoyalty = 45,
+
+    // Marketplace
+    BadFee = 50,
+    BadPrice = 51,
+    AlreadyListed = 52,
+    Listed = 53,
+    NotListed = 54,
+    NotSeller = 55,
+    ListingOwnerMismatch = 56,
+    SelfBuy = 57,
+    InsufficientForFee = 58,
+    ListingExpired = 59,
+
+    // Quản trị
+    NoPendingAdmin = 60,
+    MissingRole = 61,
+    Paused = 62,
+    BadTtlConfig = 63,
+
+    // Council
+    NoCouncil = 70,
+    BadCouncil = 71,
+    NotCouncilMember = 72,
+    ProposalNotFound = 73,
+    ProposalExpired = 74,
+    AlreadyApproved = 75,
+    ProposalExecuted = 76,
+    NotEnoughApprovals = 77,
+    CouncilRequired = 78,
+
+    // Timelock
+    TimelockRequired = 80,
+    TimelockNotFound = 81,
+    TimelockNotReady = 82,
+    BadTimelockDelay = 83,
+
+    // Mint công khai
+    MintClosed = 90,
+    MintNotStarted = 91,
+    MintEnded = 92,
+    MintLimitReached = 93,
+    BadMintConfig = 94,
+    InvalidProof = 95,
+    CommitRequired = 96,
+    CommitNotFound = 97,
+    CommitExists = 98,
+    RevealTooEarly = 99,
+    RevealWindowClosed = 100,
+    CommitNotExpired = 101,
+    DesignNotFound = 102,
+    DesignPending = 103,
+
+    // Marketplace (tiếp)
+    PriceCooldown = 110,
+    PriceAboveMax = 111,
+    ListingNonceMismatch = 112,
+}
+
+
+#[contracttype]
+#[derive(Clone)]
+pub struct NftData {
+    pub owner: Address,
+    pub pixels: Bytes,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Listing {
+    pub seller: Address,
+    pub price: i128,
+    pub expires_ledger: Option<u32>,  // None → không hết hạn
+}
+
+// Layout Listing trước schema v3 (chỉ dùng khi migrate)
+#[contracttype]
+#[derive(Clone)]
+struct ListingV2 {
+    seller: Address,
+    price: i128,
+}
+
+// Kết quả market_quote_buy: price = protocol_fee + royalty + seller_net
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketQuote {
+    pub price: i128,
+    pub protocol_fee: i128,
+    pub royalty: i128,
+    pub royalty_recipient: Option<Address>,
+    pub seller_net: i128,
+}
+
+// Flat: value = phí raw cố định / giao dịch; Bps: value = phần vạn của amount
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum FeeMode {
+    Flat = 0,
+    Bps = 1,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransferFeeConfig {
+    pub mode: FeeMode,
+    pub value: i128,
+    pub min_fee: Option<i128>,
+    pub max_fee: Option<i128>,
+}
+
+// Tác giả NFT và phần royalty (bps) trích từ mỗi lần bán qua market
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoyaltyInfo {
+    pub creator: Address,
+    pub recipient: Address,
+    pub bps: u32,
+}
+
+// Người được chuyển hộ một NFT cụ thể tới hết expiration_ledger
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftApproval {
+    pub spender: Address,
+    pub expiration_ledger: u32,
+}
+
+// Cấu hình mint_public: giá (FT raw) trả vào treasury, giới hạn mỗi ví và cửa sổ ledger
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicMintConfig {
+    pub enabled: bool,
+    pub price: i128,
+    pub wallet_limit: u32,  // 0 → không giới hạn
+    pub start_ledger: u32,
+    pub end_ledger: u32,    // 0 → không có hạn kết thúc
+}
+
+// Commit cho mint_reveal; hash = sha256(xdr(creator) ‖ pixels ‖ salt)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintCommitment {
+    pub creator: Address,
+    pub ledger: u32,
+    pub deposit: i128,
+}
+
+// Bài nộp chờ duyệt; deposit giữ ở số dư của contract tới khi approve/reject
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignSubmission {
+    pub id: u32,
+    pub creator: Address,
+    pub pixels: Bytes,
+    pub deposit: i128,
+    pub submitted_ledger: u32,
+}
+
+// Presale: leaf = sha256(xdr(to) ‖ allowance u32 big-endian), cặp node băm theo thứ tự tăng dần
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowlistConfig {
+    pub root: BytesN<32>,
+    pub price: i128,
+}
+
+// Vai trò vận hành; ADMIN quản lý (grant/revoke) và ngầm có mọi vai trò
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Role {
+    Minter = 0,         // mint_nft, nft_index_range
+    FeeManager = 1,     // listing_fee_set, transfer_fee_set, fee_exempt_set
+    PaletteManager = 2, // palette_set
+    Pauser = 3,
+}
+
+// Nhóm chức năng có thể tạm dừng; All dừng toàn bộ các nhóm còn lại.
+// Không bị chặn khi pause (cố ý): view, hàm cấu hình của admin/role, bump_* (trả rent),
+// mint_commit_refund và reject_design (để người dùng lấy lại tiền cọc), nft_admin_burn (kiểm duyệt).
+#[contracttype]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Feature {
+    All = 0,
+    TokenTransfer = 1, // transfer, transfer_batch, transfer_from, approve, burn, burn_from
+    NftTransfer = 2,   // nft_transfer, nft_transfer_from, nft_approve, nft_set_approval_for_all, nft_burn
+    Minting = 3,       // mint, mint_nft, mint_public, mint_allowlisted, mint_commit, mint_reveal,
+                       // submit_design, approve_design, nft_index_range
+    Market = 4,        // market_list_nft, market_update_price, market_cancel, market_buy,
+                       // market_buy_checked, market_prune_expired
+}
+
+// Khi TTL còn lại < threshold thì gia hạn lên extend_to (đơn vị: ledger)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TtlConfig {
+    pub instance_threshold: u32,
+    pub instance_extend_to: u32,
+    pub persistent_threshold: u32,
+    pub persistent_extend_to: u32,
+}
+
+// Hội đồng admin: cần `threshold` chữ ký trong `signers` để thực thi một AdminAction
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Council {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+// Các thao tác admin council có thể đề xuất
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AdminAction {
+    MintNft(Address, Bytes),  // (to, pixels)
+    PaletteSet(Vec<u32>),
+    ListingFeeSet(i128),
+    ProposeAdmin(Address),
+    CouncilSet(Vec<Address>, u32),  // (signers, threshold); rỗng + 0 → tắt council
+    Upgrade(BytesN<32>),            // wasm_hash
+    TtlConfigSet(TtlConfig),
+    GrantRole(Role, Address),
+    RevokeRole(Role, Address),
+    FtMint(Address, i128),          // (to, amount)
+    NftBurn(u32),
+    TimelockDelay(u32),             // tăng: áp dụng ngay; giảm: xếp hàng TimelockChange::Delay
+    TimelockCancel(u32),            // huỷ thay đổi đang chờ (kể cả do council xếp hàng)
+    TreasurySet(Address),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Proposal {
+    pub action: AdminAction,
+    pub proposer: Address,
+    pub approvals: Vec<Address>,
+    pub expires_ledger: u32,
+    pub executed: bool,
+}
+
+// Thay đổi tham số nhạy cảm phải qua hàng đợi timelock (khi TL_DELAY > 0)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimelockChange {
+    ListingFee(i128),
+    Palette(Vec<u32>),
+    Delay(u32),         // giảm độ trễ (tăng thì gọi thẳng timelock_delay_set)
+    TransferFee(TransferFeeConfig),
+    SaleFee(u32),       // bps
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScheduledChange {
+    pub id: u32,
+    pub change: TimelockChange,
+    pub scheduled_by: Address,
+    pub eta_ledger: u32,  // execute được từ ledger này
+}
+
+// Phân bổ ban đầu khi init (amount tính theo token nguyên, nhân 10^decimals)
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GenesisAllocation {
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contracttype]
+#[derive(Clone)]
+pub struct AllowanceValue {
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+
+// ========== SEP-41 events ==========
+// topics: ["approve", from, spender]  data: [amount, expiration_ledger]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Approve {
+    #[topic]
+    pub from: Address,
+    #[topic]
+    pub spender: Address,
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+// topics: ["transfer", from, to]  data: amount
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Transfer {
+    #[topic]
+    pub from: Address,
+    #[topic]
+    pub to: Address,
+    pub amount: i128,
+}
+
+// topics: ["burn", from]  data: amount
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Burn {
+    #[topic]
+    pub from: Address,
+    pub amount: i128,
+}
+
+// topics: ["mint", to]  data: amount
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mint {
+    #[topic]
+    pub to: Address,
+    pub amount: i128,
+}
+
+
+// topics: ["supply_change"]  data: {delta, total_supply}  (delta > 0: mint, < 0: burn)
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SupplyChange {
+    pub delta: i128,
+    pub total_supply: i128,
+}
+
+
+// ========== Contract events (indexer) ==========
+// Số dư FT dựng lại được chỉ từ mint / transfer / burn (mọi khoản phí cũng là
+// một transfer tới người nhận phí). Sở hữu NFT dựng lại từ nft_mint / nft_transfer,
+// listing từ market_list / market_cancel / market_buy.
+
+// topics: ["init", admin]  data: {name, symbol, decimals, supply, listing_fee}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Init {
+    #[topic]
+    pub admin: Address,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u32,
+    pub supply: i128,
+    pub listing_fee: i128,
+}
+
+// topics: ["admin_proposed", admin]  data: pending
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminProposed {
+    #[topic]
+    pub admin: Address,
+    pub pending: Address,
+}
+
+// topics: ["admin_changed", old_admin]  data: new_admin
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdminChanged {
+    #[topic]
+    pub old_admin: Address,
+    pub new_admin: Address,
+}
+
+// topics: ["role_granted", role, account]  data: {}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleGranted {
+    #[topic]
+    pub role: Role,
+    #[topic]
+    pub account: Address,
+}
+
+// topics: ["role_revoked", role, account]  data: {}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoleRevoked {
+    #[topic]
+    pub role: Role,
+    #[topic]
+    pub account: Address,
+}
+
+// topics: ["pause_set", feature]  data: paused
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PauseSet {
+    #[topic]
+    pub feature: Feature,
+    pub paused: bool,
+}
+
+// topics: ["council_set"]  data: [signers, threshold]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CouncilSet {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+// topics: ["proposal_created", id, proposer]  data: [action, expires_ledger]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProposalCreated {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub proposer: Address,
+    pub action: AdminAction,
+    pub expires_ledger: u32,
+}
+
+// topics: ["proposal_approved", id, signer]  data: approvals
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProposalApproved {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub signer: Address,
+    pub approvals: u32,
+}
+
+// topics: ["proposal_executed", id]  data: action
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProposalExecuted {
+    #[topic]
+    pub id: u32,
+    pub action: AdminAction,
+}
+
+// topics: ["timelock_delay_set"]  data: [old_delay, new_delay]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelockDelaySet {
+    pub old_delay: u32,
+    pub new_delay: u32,
+}
+
+// topics: ["timelock_scheduled", id]  data: [change, eta_ledger]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelockScheduled {
+    #[topic]
+    pub id: u32,
+    pub change: TimelockChange,
+    pub eta_ledger: u32,
+}
+
+// topics: ["timelock_cancelled", id]  data: {}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelockCancelled {
+    #[topic]
+    pub id: u32,
+}
+
+// topics: ["timelock_executed", id]  data: change
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TimelockExecuted {
+    #[topic]
+    pub id: u32,
+    pub change: TimelockChange,
+}
+
+// topics: ["upgraded"]  data: wasm_hash
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Upgraded {
+    pub wasm_hash: BytesN<32>,
+}
+
+// topics: ["migrated"]  data: [from_version, to_version]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Migrated {
+    pub from_version: u32,
+    pub to_version: u32,
+}
+
+// topics: ["ttl_config_set"]  data: TtlConfig
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TtlConfigSet {
+    pub config: TtlConfig,
+}
+
+// topics: ["nft_mint", id, to]  data: pixels
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftMint {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub to: Address,
+    pub pixels: Bytes,
+}
+
+// topics: ["public_mint_set"]  data: PublicMintConfig
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicMintSet {
+    pub config: PublicMintConfig,
+}
+
+// topics: ["mint_committed", creator]  data: [hash, deposit]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintCommitted {
+    #[topic]
+    pub creator: Address,
+    pub hash: BytesN<32>,
+    pub deposit: i128,
+}
+
+// topics: ["mint_revealed", creator]  data: [hash, id]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintRevealed {
+    #[topic]
+    pub creator: Address,
+    pub hash: BytesN<32>,
+    pub id: u32,
+}
+
+// topics: ["mint_commit_refunded", creator]  data: [hash, amount]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintCommitRefunded {
+    #[topic]
+    pub creator: Address,
+    pub hash: BytesN<32>,
+    pub amount: i128,
+}
+
+// topics: ["mint_commit_required_set"]  data: required
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintCommitRequiredSet {
+    pub required: bool,
+}
+
+// topics: ["design_submitted", id, creator]  data: pixels
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignSubmitted {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub creator: Address,
+    pub pixels: Bytes,
+}
+
+// topics: ["design_fee_set"]  data: [old_fee, new_fee]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignFeeSet {
+    pub old_fee: i128,
+    pub new_fee: i128,
+}
+
+// topics: ["design_approved", id]  data: nft_id
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignApproved {
+    #[topic]
+    pub id: u32,
+    pub nft_id: u32,
+}
+
+// topics: ["design_rejected", id]  data: reason
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DesignRejected {
+    #[topic]
+    pub id: u32,
+    pub reason: Option<u32>,
+}
+
+// topics: ["allowlist_set"]  data: Option<AllowlistConfig> (None → đóng presale)
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowlistSet {
+    pub config: Option<AllowlistConfig>,
+}
+
+// topics: ["royalty_default_set"]  data: bps
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoyaltyDefaultSet {
+    pub bps: u32,
+}
+
+// topics: ["royalty_recipient_set", id, creator]  data: recipient
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoyaltyRecipientSet {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub creator: Address,
+    pub recipient: Address,
+}
+
+// topics: ["royalty_paid", id, recipient]  data: amount
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoyaltyPaid {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub recipient: Address,
+    pub amount: i128,
+}
+
+// topics: ["nft_burn", id, owner]  data: pixels
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftBurn {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub owner: Address,
+    pub pixels: Bytes,
+}
+
+// topics: ["nft_transfer", id, from]  data: to
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftTransfer {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub from: Address,
+    pub to: Address,
+}
+
+// topics: ["nft_approve", owner, id]  data: [spender, expiration_ledger]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftApprove {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub id: u32,
+    pub spender: Address,
+    pub expiration_ledger: u32,
+}
+
+// topics: ["nft_approval_for_all", owner, operator]  data: approved
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftApprovalForAll {
+    #[topic]
+    pub owner: Address,
+    #[topic]
+    pub operator: Address,
+    pub approved: bool,
+}
+
+// topics: ["nft_index", id]  data: [start, end]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NftIndex {
+    #[topic]
+    pub id: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+// topics: ["palette_set"]  data: palette (Vec<u32> 32 màu)
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PaletteSet {
+    pub palette: Vec<u32>,
+}
+
+// topics: ["listing_fee_set"]  data: {old_fee, new_fee}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListingFeeSet {
+    pub old_fee: i128,
+    pub new_fee: i128,
+}
+
+// topics: ["sale_fee_set"]  data: [old_bps, new_bps]
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SaleFeeSet {
+    pub old_bps: u32,
+    pub new_bps: u32,
+}
+
+// topics: ["price_cooldown_set"]  data: [old, new]  (đơn vị: ledger)
+#[contractevent(data_format = "vec")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PriceCooldownSet {
+    pub old: u32,
+    pub new: u32,
+}
+
+// topics: ["transfer_fee_set"]  data: TransferFeeConfig
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TransferFeeSet {
+    pub config: TransferFeeConfig,
+}
+
+// topics: ["treasury_set"]  data: treasury
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreasurySet {
+    pub treasury: Address,
+}
+
+// topics: ["fee_exempt_set", account]  data: exempt
+#[contractevent(data_format = "single-value")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeeExemptSet {
+    #[topic]
+    pub account: Address,
+    pub exempt: bool,
+}
+
+// topics: ["market_list", id, seller]  data: {price, fee}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketList {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub seller: Address,
+    pub price: i128,
+    pub fee: i128,
+}
+
+// topics: ["market_cancel", id, seller]  data: {}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketCancel {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub seller: Address,
+}
+
+// topics: ["market_price_changed", id, seller]  data: {old_price, new_price}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketPriceChanged {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub seller: Address,
+    pub old_price: i128,
+    pub new_price: i128,
+}
+
+// topics: ["market_pruned", id, seller]  data: {}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketPruned {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub seller: Address,
+}
+
+// topics: ["market_buy", id, buyer]  data: {seller, price}
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MarketBuy {
+    #[topic]
+    pub id: u32,
+    #[topic]
+    pub buyer: Address,
+    pub seller: Address,
+    pub price: i128,
+}
+
+
+#[contract]
+pub struct SimpleTokenNft;
+
+#[contractimpl]
+impl SimpleTokenNft {
+
+    // -------------------------------------------------------------------------
+    // init
+    //   - decimals: 0..=18.
+    //   - initial_supply, allocations[i].amount: số token nguyên, nhân 10^decimals.
+    //   - allocations (tuỳ chọn) cấp trước cho từng địa chỉ, phần còn lại về admin.
+    //   - max_supply (tuỳ chọn, token nguyên): trần tổng cung, không đổi được sau init.
+    // -------------------------------------------------------------------------
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(
+        env: Env,
+        admin: Address,
+        name: String,
+        symbol: String,
+        decimals: u32,
+        initial_supply: i128,
+        allocations: Option<Vec<GenesisAllocation>>,
+        max_supply: Option<i128>,
+    ) -> Result<(), Error> {
+        if env.storage().instance().has(&INITED) { return Err(Error::AlreadyInit); }
+        admin.require_auth();
+
+        if decimals > MAX_DECIMALS { return Err(Error::BadDecimals); }
+        if initial_supply < 0 { return Err(Error::BadSupply); }
+
+        // Tổng cung (raw) = initial_supply * 10^decimals
+        let total: i128 = mul_pow10_i128(initial_supply, decimals).ok_or(Error::SupplyOverflow)?;
+        let cap: Option<i128> = match max_supply {
+            Some(m) => Some(mul_pow10_i128(m, decimals).ok_or(Error::SupplyOverflow)?),
+            None => None,
+        };
+        if cap.is_some_and(|c| total > c) { return Err(Error::MaxSupplyExceeded); }
+
+        // Quy đổi phân bổ sang raw, tổng không vượt total
+        let allocations = allocations.unwrap_or(Vec::new(&env));
+        let mut raw_allocs: Vec<(Address, i128)> = Vec::new(&env);
+        let mut allocated: i128 = 0;
+        for a in allocations.iter() {
+            if a.amount <= 0 { return Err(Error::BadAmount); }
+            let raw = mul_pow10_i128(a.amount, decimals).ok_or(Error::SupplyOverflow)?;
+            allocated = allocated.checked_add(raw).ok_or(Error::SupplyOverflow)?;
+            raw_allocs.push_back((a.to, raw));
+        }
+        if allocated > total { return Err(Error::AllocationExceedsSupply); }
+
+        // Lưu metadata token
+        env.storage().instance().set(&ADMIN, &admin);
+        env.storage().instance().set(&NAME, &name);
+        env.storage().instance().set(&SYMBOL_, &symbol);
+        env.storage().instance().set(&DECIMALS, &decimals);
+        env.storage().instance().set(&SUPPLY, &total);
+        if let Some(c) = cap { env.storage().instance().set(&MAX_SUP, &c); }
+        env.storage().instance().set(&INITED, &true);
+        env.storage().instance().set(&VERSION, &SCHEMA_VERSION);
+
+        // Cấp FT theo phân bổ, phần còn lại cho admin
+        for (to, raw) in raw_allocs.iter() {
+            let bal: i128 = persist_get(&env, &(BAL, &to)).unwrap_or(0);
+            persist_set(&env, &(BAL, &to), &(bal + raw));
+        }
+        let rest = total - allocated;
+        let admin_bal: i128 = persist_get(&env, &(BAL, &admin)).unwrap_or(0);
+        persist_set(&env, &(BAL, &admin), &(admin_bal + rest));
+
+        // Palette & marketplace defaults
+        env.storage().instance().set(&PAL, &default_palette(&env));
+        env.storage().instance().set(&NFT_SUP, &0i128);
+        env.storage().instance().set(&NEXT_ID, &0i128);
+        env.storage().instance().set(&LSTFEE, &1i128); // 1 raw
+        let empty_ids: Vec<u32> = Vec::new(&env);
+        persist_set(&env, &LIDS, &empty_ids);
+
+        Init { admin: admin.clone(), name, symbol, decimals, supply: total, listing_fee: 1 }.publish(&env);
+        for (to, raw) in raw_allocs.iter() {
+            Mint { to, amount: raw }.publish(&env);
+        }
+        if rest > 0 {
+            Mint { to: admin, amount: rest }.publish(&env);
+        }
+
+        log!(&env, "INIT OK supply={} decimals={} allocated={}", total, decimals, allocated);
+        Ok(())
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Chuyển giao admin 2 bước
+     |   - propose_admin: admin hiện tại đề cử (đề cử mới ghi đè đề cử cũ).
+     |   - accept_admin: địa chỉ được đề cử ký nhận → trở thành ADMIN.
+     *-------------------------------------------------------------------------*/
+    pub fn propose_admin(env: Env, new_admin: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        env.storage().instance().set(&PEND_ADM, &new_admin);
+        AdminProposed { admin, pending: new_admin }.publish(&env);
+        Ok(())
+    }
+
+    pub fn accept_admin(env: Env) -> Result<(), Error> {
+        require_inited(&env)?;
+        let pending: Address = env.storage().instance().get(&PEND_ADM).ok_or(Error::NoPendingAdmin)?;
+        pending.require_auth();
+
+        let old_admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        env.storage().instance().set(&ADMIN, &pending);
+        env.storage().instance().remove(&PEND_ADM);
+        AdminChanged { old_admin, new_admin: pending }.publish(&env);
+        Ok(())
+    }
+
+    pub fn admin(env: Env) -> Result<Address, Error> {
+        env.storage().instance().get(&ADMIN).ok_or(Error::NotInited)
+    }
+
+    pub fn pending_admin(env: Env) -> Option<Address> {
+        env.storage().instance().get(&PEND_ADM)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Council M-of-N (tuỳ chọn)
+     |   - council_set (ADMIN): lập council lần đầu; signers rỗng và threshold = 0
+     |     để tắt. Khi đã có council, đổi / tắt chỉ qua AdminAction::CouncilSet.
+     |   - Khi có council: mọi entrypoint chỉ-ADMIN (upgrade, mint, treasury_set,
+     |     grant/revoke_role, ttl_config_set, timelock_delay_set, nft_admin_burn,
+     |     propose_admin) cùng mint_nft, palette_set, listing_fee_set (và timelock
+     |     cho LSTFEE / palette) trả CouncilRequired, kể cả với người có vai trò →
+     |     chỉ thực hiện được qua council_execute. ADMIN mất quyền ngầm ở require_role.
+     |   - council_propose: signer đề xuất AdminAction (tự tính 1 chữ ký),
+     |     hết hạn sau expires_ledger.
+     |   - council_approve: signer khác ký thêm.
+     |   - council_execute: ai cũng gọi được khi đủ threshold chữ ký của
+     |     council hiện tại và proposal chưa hết hạn.
+     *-------------------------------------------------------------------------*/
+    pub fn council_set(env: Env, signers: Vec<Address>, threshold: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_council(&env, signers, threshold)
+    }
+
+    pub fn council_get(env: Env) -> Option<Council> {
+        env.storage().instance().get(&COUNCIL)
+    }
+
+    pub fn council_propose(env: Env, signer: Address, action: AdminAction, expires_ledger: u32) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_council_member(&env, &signer)?;
+        if expires_ledger < env.ledger().sequence() { return Err(Error::BadExpirationLedger); }
+
+        let id: u32 = env.storage().instance().get(&PROP_NID).unwrap_or(0);
+        let prop = Proposal {
+            action: action.clone(),
+            proposer: signer.clone(),
+            approvals: Vec::from_array(&env, [signer.clone()]),
+            expires_ledger,
+            executed: false,
+        };
+        persist_set(&env, &(PROP, id), &prop);
+        env.storage().instance().set(&PROP_NID, &(id + 1));
+        ProposalCreated { id, proposer: signer, action, expires_ledger }.publish(&env);
+        Ok(id)
+    }
+
+    // Trả về số chữ ký hiện có
+    pub fn council_approve(env: Env, signer: Address, id: u32) -> Result<u32, Error> {
+        require_inited(&env)?;
+        let council = require_council_member(&env, &signer)?;
+        let mut prop = load_open_proposal(&env, id)?;
+        if prop.approvals.contains(&signer) { return Err(Error::AlreadyApproved); }
+
+        prop.approvals.push_back(signer.clone());
+        persist_set(&env, &(PROP, id), &prop);
+        let approvals = count_approvals(&council, &prop.approvals);
+        ProposalApproved { id, signer, approvals }.publish(&env);
+        Ok(approvals)
+    }
+
+    pub fn council_execute(env: Env, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let council = read_council(&env)?;
+        let mut prop = load_open_proposal(&env, id)?;
+        if count_approvals(&council, &prop.approvals) < council.threshold {
+            return Err(Error::NotEnoughApprovals);
+        }
+
+        prop.executed = true;
+        persist_set(&env, &(PROP, id), &prop);
+        execute_action(&env, prop.action.clone())?;
+        ProposalExecuted { id, action: prop.action }.publish(&env);
+        Ok(())
+    }
+
+    pub fn council_proposal(env: Env, id: u32) -> Option<Proposal> {
+        persist_get(&env, &(PROP, id))
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Upgrade & migrate (ADMIN)
+     |   - upgrade: thay wasm, giữ nguyên storage.
+     |   - migrate: chuyển storage từ schema đang lưu lên SCHEMA_VERSION.
+     |     Sau upgrade, mọi entrypoint ghi trả MigrationRequired cho tới khi migrate.
+     *-------------------------------------------------------------------------*/
+    pub fn upgrade(env: Env, wasm_hash: BytesN<32>) -> Result<(), Error> {
+        if !env.storage().instance().has(&INITED) { return Err(Error::NotInited); }
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_wasm(&env, wasm_hash);
+        Ok(())
+    }
+
+    // Trả về phiên bản schema sau khi migrate (không làm gì nếu đã mới nhất).
+    // Có council: ai cũng gọi được — wasm mới đã được council duyệt qua AdminAction::Upgrade,
+    // còn council_* bị chặn (MigrationRequired) cho tới khi migrate xong.
+    pub fn migrate(env: Env) -> Result<u32, Error> {
+        if !env.storage().instance().has(&INITED) { return Err(Error::NotInited); }
+        if !env.storage().instance().has(&COUNCIL) {
+            let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+            admin.require_auth();
+        }
+
+        let from_version = read_version(&env);
+        if from_version >= SCHEMA_VERSION { return Ok(from_version); }
+        for v in from_version..SCHEMA_VERSION {
+            migrate_step(&env, v);
+        }
+        env.storage().instance().set(&VERSION, &SCHEMA_VERSION);
+        Migrated { from_version, to_version: SCHEMA_VERSION }.publish(&env);
+        Ok(SCHEMA_VERSION)
+    }
+
+    pub fn schema_version(env: Env) -> u32 {
+        read_version(&env)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | TTL / rent
+     |   - Mọi đường đọc/ghi persistent tự gia hạn theo TtlConfig, instance được
+     |     gia hạn ở mọi entrypoint ghi.
+     |   - bump_nft / bump_account: ai cũng gọi được (tự trả phí) để gia hạn đủ
+     |     persistent_extend_to cho các entry liên quan.
+     |   - Contract không đọc được TTL còn lại; dApp lấy liveUntilLedgerSeq qua RPC
+     |     getLedgerEntries (xem readNftTtl / readAccountTtl trong stellar.ts).
+     *-------------------------------------------------------------------------*/
+    pub fn ttl_config_set(env: Env, config: TtlConfig) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_ttl_config(&env, config)
+    }
+
+    pub fn ttl_config_get(env: Env) -> TtlConfig {
+        ttl_config(&env)
+    }
+
+    // Gia hạn NFT(id), UNIQ(pixels) và listing (nếu có)
+    pub fn bump_nft(env: Env, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let data: NftData = env.storage().persistent().get(&(NFT, id)).ok_or(Error::NftNotFound)?;
+        let extend_to = ttl_config(&env).persistent_extend_to;
+        persist_bump(&env, &(NFT, id), extend_to);
+        persist_bump(&env, &(UNIQ, data.pixels), extend_to);
+        persist_bump(&env, &(LST, id), extend_to);
+        Ok(())
+    }
+
+    // Gia hạn số dư FT và danh sách NFT sở hữu của `account`
+    pub fn bump_account(env: Env, account: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        let extend_to = ttl_config(&env).persistent_extend_to;
+        persist_bump(&env, &(BAL, &account), extend_to);
+        persist_bump(&env, &(OWN, &account), extend_to);
+        Ok(())
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Roles (ADMIN là role admin; có council → chỉ qua GrantRole / RevokeRole,
+     | và ADMIN không còn mặc nhiên có mọi vai trò)
+     *-------------------------------------------------------------------------*/
+    pub fn grant_role(env: Env, role: Role, account: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_role(&env, role, account, true);
+        Ok(())
+    }
+
+    pub fn revoke_role(env: Env, role: Role, account: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_role(&env, role, account, false);
+        Ok(())
+    }
+
+    pub fn has_role(env: Env, role: Role, account: Address) -> bool {
+        has_role(&env, role, &account)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Pause (Role::Pauser)
+     |   - Chỉ chặn các entrypoint thay đổi trạng thái của nhóm tương ứng;
+     |     view và các hàm quản trị (phí, role, palette...) vẫn chạy.
+     *-------------------------------------------------------------------------*/
+    pub fn pause_set(env: Env, pauser: Address, feature: Feature, paused: bool) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::Pauser, &pauser)?;
+        if paused {
+            env.storage().instance().set(&(PAUSE, feature), &true);
+        } else {
+            env.storage().instance().remove(&(PAUSE, feature));
+        }
+        PauseSet { feature, paused }.publish(&env);
+        Ok(())
+    }
+
+    // true nếu `feature` hoặc All đang tạm dừng
+    pub fn is_paused(env: Env, feature: Feature) -> bool {
+        is_paused(&env, feature)
+    }
+
+    // transfer (FT) — người gửi trả phí theo TransferFeeConfig (mặc định 1 raw)
+    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        if amount <= 0 { return Err(Error::BadAmount); }
+        from.require_auth();
+        if from == to { return Err(Error::SelfTransfer); }
+
+        let fee = transfer_with_fee(&env, &from, &to, amount)?;
+
+        log!(&env, "TRANSFER: {} -> {} amount={} fee_raw={}", from, to, amount, fee);
+        Ok(())
+    }
+
+    // -------------------------------------------------------------------------
+    // transfer_batch
+    //   - Gửi token tới nhiều địa chỉ trong 1 lần gọi.
+    //   - Người gửi trả phí cho từng người nhận theo TransferFeeConfig.
+    //   - Tổng khấu trừ = sum(amounts) + sum(fee(amount_i)).
+    //   - Luôn cộng tổng phí cho treasury để bảo toàn tổng cung.
+    //   - Trả về tổng phí đã thu (raw).
+    // -------------------------------------------------------------------------
+    pub fn transfer_batch(env: Env, from: Address, tos: Vec<Address>, amounts: Vec<i128>) -> Result<i128, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        from.require_auth();
+
+        let n = tos.len();
+        if n == 0 { return Err(Error::EmptyBatch); }
+        if n != amounts.len() { return Err(Error::LengthMismatch); }
+
+        // Validate inputs & tính tổng amount, tổng phí (phí tính riêng cho từng amount)
+        let cfg = read_fee_config(&env);
+        let exempt = is_fee_exempt(&env, &from);
+        let mut sum_amounts: i128 = 0;
+        let mut total_fee: i128 = 0;
+        for i in 0..n {
+            let to = tos.get_unchecked(i);
+            let amt = amounts.get_unchecked(i);
+            if amt <= 0 { return Err(Error::BadAmountAt); }
+            if to == from { return Err(Error::SelfTransferAt); }
+            sum_amounts = sum_amounts.checked_add(amt).ok_or(Error::AmountSumOverflow)?;
+            if !exempt {
+                total_fee = total_fee.checked_add(quote_fee(&cfg, amt)?).ok_or(Error::FeeAddOverflow)?;
+            }
+        }
+
+        let treasury = fee_recipient(&env);
+        let total_deduct: i128 = sum_amounts.checked_add(total_fee).ok_or(Error::TotalDeductOverflow)?;
+
+        // Kiểm tra số dư người gửi
+        let from_bal: i128 = persist_get(&env, &(BAL, &from)).unwrap_or(0);
+        if from_bal < total_deduct { return Err(Error::InsufficientBalanceWithFee); }
+
+        // Cộng cho từng người nhận
+        for i in 0..n {
+            let to = tos.get_unchecked(i);
+            let amt = amounts.get_unchecked(i);
+            let to_bal: i128 = persist_get(&env, &(BAL, &to)).unwrap_or(0);
+            let new_to = to_bal.checked_add(amt).ok_or(Error::BalOverflow)?;
+            persist_set(&env, &(BAL, &to), &new_to);
+            Transfer { from: from.clone(), to, amount: amt }.publish(&env);
+        }
+
+        // Khấu trừ người gửi (một lần)
+        let new_from = from_bal - total_deduct;
+        persist_set(&env, &(BAL, &from), &new_from);
+
+        // Cộng tổng phí cho treasury (một lần)
+        if total_fee > 0 {
+            let treasury_bal: i128 = persist_get(&env, &(BAL, &treasury)).unwrap_or(0);
+            let new_treasury = treasury_bal.checked_add(total_fee).ok_or(Error::BalOverflow)?;
+            persist_set(&env, &(BAL, &treasury), &new_treasury);
+            Transfer { from: from.clone(), to: treasury, amount: total_fee }.publish(&env);
+        }
+
+        log!(&env, "TRANSFER_BATCH: from={} n_recipients={} sum_amounts={} total_fee_raw={}", from, n, sum_amounts, total_fee);
+        Ok(total_fee)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | SEP-41: allowance / approve / transfer_from / burn / burn_from
+     |   - Allowance lưu ở temporary storage, hết hạn sau expiration_ledger.
+     |   - transfer_from tính phí như transfer: `from` trả amount + fee(amount)
+     |     từ số dư, allowance chỉ bị trừ đúng amount (chuẩn SEP-41).
+     |   - burn / burn_from không tính phí, giảm SUPPLY tương ứng.
+     *-------------------------------------------------------------------------*/
+    pub fn allowance(env: Env, from: Address, spender: Address) -> i128 {
+        read_allowance(&env, &from, &spender).amount
+    }
+
+    pub fn approve(env: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        from.require_auth();
+        if amount < 0 { return Err(Error::BadAmount); }
+        write_allowance(&env, &from, &spender, amount, expiration_ledger)?;
+
+        Approve { from, spender, amount, expiration_ledger }.publish(&env);
+        Ok(())
+    }
+
+    pub fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        if amount <= 0 { return Err(Error::BadAmount); }
+        spender.require_auth();
+        if from == to { return Err(Error::SelfTransfer); }
+
+        spend_allowance(&env, &from, &spender, amount)?;
+        let fee = transfer_with_fee(&env, &from, &to, amount)?;
+
+        log!(&env, "TRANSFER_FROM: spender={} {} -> {} amount={} fee_raw={}", spender, from, to, amount, fee);
+        Ok(())
+    }
+
+    pub fn burn(env: Env, from: Address, amount: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        if amount <= 0 { return Err(Error::BadAmount); }
+        from.require_auth();
+        burn_balance(&env, &from, amount)
+    }
+
+    pub fn burn_from(env: Env, spender: Address, from: Address, amount: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::TokenTransfer)?;
+        if amount <= 0 { return Err(Error::BadAmount); }
+        spender.require_auth();
+        spend_allowance(&env, &from, &spender, amount)?;
+        burn_balance(&env, &from, amount)
+    }
+
+    // mint (ADMIN) — phát hành thêm FT (raw), không vượt MAX_SUP nếu có
+    pub fn mint(env: Env, to: Address, amount: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        if amount <= 0 { return Err(Error::BadAmount); }
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        ft_mint(&env, to, amount)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | mint_nft (Role::Minter)
+     *-------------------------------------------------------------------------*/
+    pub fn mint_nft(env: Env, minter: Address, to: Address, pixels: Bytes) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        require_role(&env, Role::Minter, &minter)?;
+        require_no_council(&env)?;
+        mint_nft_internal(&env, to, pixels)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | mint_public: ai cũng mint được tranh của mình khi đang mở bán
+     |   - Mặc định commit-reveal bắt buộc (CommitRequired); Role::Minter tắt
+     |     bằng mint_commit_required_set(false) nếu chấp nhận rủi ro bị copy pixel.
+     |   - creator trả `price` FT cho treasury (không tính phí transfer).
+     |   - Cùng kiểm tra 81 pixel / UNIQ như mint_nft.
+     |   - public_mint_set (Role::Minter) bật/tắt, đặt giá, giới hạn, cửa sổ ledger.
+     *-------------------------------------------------------------------------*/
+    pub fn mint_public(env: Env, creator: Address, pixels: Bytes) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        creator.require_auth();
+        if commit_required(&env) { return Err(Error::CommitRequired); }
+
+        let (cfg, minted) = check_public_mint(&env, &creator)?;
+        let id = mint_nft_internal(&env, creator.clone(), pixels)?;
+        if cfg.price > 0 {
+            move_balance(&env, &creator, &fee_recipient(&env), cfg.price)?;
+        }
+        persist_set(&env, &(PM_CNT, &creator), &(minted + 1));
+        Ok(id)
+    }
+
+    pub fn public_mint_set(env: Env, manager: Address, config: PublicMintConfig) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::Minter, &manager)?;
+        if config.price < 0 { return Err(Error::BadMintConfig); }
+        if config.end_ledger != 0 && config.end_ledger < config.start_ledger { return Err(Error::BadMintConfig); }
+        env.storage().instance().set(&PMINT, &config);
+        PublicMintSet { config }.publish(&env);
+        Ok(())
+    }
+
+    pub fn public_mint_get(env: Env) -> PublicMintConfig {
+        read_public_mint(&env)
+    }
+
+    // Số NFT `creator` đã mint qua mint_public / mint_reveal
+    pub fn public_minted_of(env: Env, creator: Address) -> u32 {
+        persist_get(&env, &(PM_CNT, &creator)).unwrap_or(0)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Commit-reveal cho mint công khai (chống copy pixel trong mempool)
+     |   - mint_commit: gửi hash, đặt cọc `price` hiện tại vào contract.
+     |   - mint_reveal: trong [commit + REVEAL_MIN_DELAY, commit + REVEAL_WINDOW]
+     |     gửi pixels + salt → mint cho creator, cọc chuyển cho treasury.
+     |   - mint_commit_refund: quá cửa sổ mà chưa reveal → hoàn cọc.
+     |   - Reveal thắng bài submit_design cùng pattern nộp sau commit (gỡ + hoàn cọc).
+     |   - mint_commit_required_set (Role::Minter): bật (mặc định) / tắt bắt buộc
+     |     commit-reveal; khi bật, mint_public và mint_allowlisted trả CommitRequired
+     |     (presale chỉ chạy khi đã tắt).
+     *-------------------------------------------------------------------------*/
+    pub fn mint_commit(env: Env, creator: Address, hash: BytesN<32>) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        creator.require_auth();
+        let (cfg, _) = check_public_mint(&env, &creator)?;
+
+        let key = (CMT, creator.clone(), hash.clone());
+        if env.storage().persistent().has(&key) { return Err(Error::CommitExists); }
+        if cfg.price > 0 {
+            move_balance(&env, &creator, &env.current_contract_address(), cfg.price)?;
+        }
+        let commit = MintCommitment { creator: creator.clone(), ledger: env.ledger().sequence(), deposit: cfg.price };
+        persist_set(&env, &key, &commit);
+        MintCommitted { creator, hash, deposit: cfg.price }.publish(&env);
+        Ok(())
+    }
+
+    pub fn mint_reveal(env: Env, creator: Address, pixels: Bytes, salt: BytesN<32>) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        creator.require_auth();
+
+        let hash = commit_hash(&env, &creator, &pixels, &salt);
+        let key = (CMT, creator.clone(), hash.clone());
+        let commit: MintCommitment = persist_get(&env, &key).ok_or(Error::CommitNotFound)?;
+        let seq = env.ledger().sequence();
+        if seq < commit.ledger + REVEAL_MIN_DELAY { return Err(Error::RevealTooEarly); }
+        if seq > commit.ledger + REVEAL_WINDOW { return Err(Error::RevealWindowClosed); }
+
+        let cfg = read_public_mint(&env);
+        let minted: u32 = persist_get(&env, &(PM_CNT, &creator)).unwrap_or(0);
+        if cfg.wallet_limit != 0 && minted >= cfg.wallet_limit { return Err(Error::MintLimitReached); }
+
+        // Bài nộp cùng pattern sau thời điểm commit (vd. copy pixels từ mempool) → reveal thắng,
+        // bài nộp bị gỡ và hoàn cọc; bài nộp trước commit vẫn giữ chỗ
+        if let Some(sub_id) = persist_get::<_, u32>(&env, &(DSGN_UNQ, &pixels)) {
+            let sub: DesignSubmission = persist_get(&env, &(DSGN, sub_id)).ok_or(Error::DesignNotFound)?;
+            if sub.submitted_ledger < commit.ledger { return Err(Error::DesignPending); }
+            remove_design(&env, &sub);
+            if sub.deposit > 0 {
+                move_balance(&env, &env.current_contract_address(), &sub.creator, sub.deposit)?;
+            }
+            DesignRejected { id: sub_id, reason: None }.publish(&env);
+        }
+
+        let id = mint_nft_internal(&env, creator.clone(), pixels)?;
+        if commit.deposit > 0 {
+            move_balance(&env, &env.current_contract_address(), &fee_recipient(&env), commit.deposit)?;
+        }
+        env.storage().persistent().remove(&key);
+        persist_set(&env, &(PM_CNT, &creator), &(minted + 1));
+        MintRevealed { creator, hash, id }.publish(&env);
+        Ok(id)
+    }
+
+    pub fn mint_commit_refund(env: Env, creator: Address, hash: BytesN<32>) -> Result<i128, Error> {
+        require_inited(&env)?;
+        creator.require_auth();
+        let key = (CMT, creator.clone(), hash.clone());
+        let commit: MintCommitment = persist_get(&env, &key).ok_or(Error::CommitNotFound)?;
+        if env.ledger().sequence() <= commit.ledger + REVEAL_WINDOW { return Err(Error::CommitNotExpired); }
+
+        env.storage().persistent().remove(&key);
+        if commit.deposit > 0 {
+            move_balance(&env, &env.current_contract_address(), &creator, commit.deposit)?;
+        }
+        MintCommitRefunded { creator, hash, amount: commit.deposit }.publish(&env);
+        Ok(commit.deposit)
+    }
+
+    pub fn mint_commit_get(env: Env, creator: Address, hash: BytesN<32>) -> Option<MintCommitment> {
+        persist_get(&env, &(CMT, creator, hash))
+    }
+
+    pub fn mint_commit_required_set(env: Env, manager: Address, required: bool) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::Minter, &manager)?;
+        env.storage().instance().set(&CMT_REQ, &required);
+        MintCommitRequiredSet { required }.publish(&env);
+        Ok(())
+    }
+
+    pub fn mint_commit_required(env: Env) -> bool {
+        commit_required(&env)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Hàng đợi duyệt tranh
+     |   - submit_design: nghệ sĩ nộp 9×9, đặt cọc DSGN_FEE; pattern được giữ chỗ
+     |     để không ai nộp trùng.
+     |   - approve_design (Role::Minter): mint cho creator như mint_nft, cọc về treasury.
+     |   - reject_design (Role::Minter): hoàn cọc, kèm mã lý do tuỳ chọn.
+     *-------------------------------------------------------------------------*/
+    pub fn submit_design(env: Env, creator: Address, pixels: Bytes) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        creator.require_auth();
+        validate_pixels(&pixels)?;
+        if env.storage().persistent().has(&(UNIQ, &pixels)) { return Err(Error::DuplicateValue); }
+        if env.storage().persistent().has(&(DSGN_UNQ, &pixels)) { return Err(Error::DesignPending); }
+
+        let deposit: i128 = env.storage().instance().get(&DSGN_FEE).unwrap_or(0);
+        if deposit > 0 {
+            move_balance(&env, &creator, &env.current_contract_address(), deposit)?;
+        }
+
+        let id: u32 = env.storage().instance().get(&DSGN_NID).unwrap_or(0);
+        let sub = DesignSubmission {
+            id,
+            creator: creator.clone(),
+            pixels: pixels.clone(),
+            deposit,
+            submitted_ledger: env.ledger().sequence(),
+        };
+        persist_set(&env, &(DSGN, id), &sub);
+        persist_set(&env, &(DSGN_UNQ, &pixels), &id);
+        let mut ids: Vec<u32> = persist_get(&env, &DSGN_IDS).unwrap_or(Vec::new(&env));
+        ids.push_back(id);
+        persist_set(&env, &DSGN_IDS, &ids);
+        env.storage().instance().set(&DSGN_NID, &(id + 1));
+
+        DesignSubmitted { id, creator, pixels }.publish(&env);
+        Ok(id)
+    }
+
+    // Trả về id NFT vừa mint
+    pub fn approve_design(env: Env, manager: Address, id: u32) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        require_role(&env, Role::Minter, &manager)?;
+        let sub: DesignSubmission = persist_get(&env, &(DSGN, id)).ok_or(Error::DesignNotFound)?;
+
+        // Gỡ giữ chỗ DSGN_UNQ trước để mint_nft_internal cho phép pattern này
+        remove_design(&env, &sub);
+        let nft_id = mint_nft_internal(&env, sub.creator.clone(), sub.pixels)?;
+        if sub.deposit > 0 {
+            move_balance(&env, &env.current_contract_address(), &fee_recipient(&env), sub.deposit)?;
+        }
+        DesignApproved { id, nft_id }.publish(&env);
+        Ok(nft_id)
+    }
+
+    pub fn reject_design(env: Env, manager: Address, id: u32, reason: Option<u32>) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::Minter, &manager)?;
+        let sub: DesignSubmission = persist_get(&env, &(DSGN, id)).ok_or(Error::DesignNotFound)?;
+
+        remove_design(&env, &sub);
+        if sub.deposit > 0 {
+            move_balance(&env, &env.current_contract_address(), &sub.creator, sub.deposit)?;
+        }
+        DesignRejected { id, reason }.publish(&env);
+        Ok(())
+    }
+
+    pub fn design_get(env: Env, id: u32) -> Option<DesignSubmission> {
+        persist_get(&env, &(DSGN, id))
+    }
+
+    pub fn design_pending_ids(env: Env) -> Vec<u32> {
+        persist_get(&env, &DSGN_IDS).unwrap_or(Vec::new(&env))
+    }
+
+    pub fn design_fee_set(env: Env, manager: Address, fee: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        if fee < 0 { return Err(Error::BadFee); }
+        require_role(&env, Role::Minter, &manager)?;
+        let old_fee: i128 = env.storage().instance().get(&DSGN_FEE).unwrap_or(0);
+        env.storage().instance().set(&DSGN_FEE, &fee);
+        DesignFeeSet { old_fee, new_fee: fee }.publish(&env);
+        Ok(())
+    }
+
+    pub fn design_fee_get(env: Env) -> i128 {
+        env.storage().instance().get(&DSGN_FEE).unwrap_or(0)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Presale allowlist (Merkle)
+     |   - allowlist_set (Role::Minter): đặt root + giá, None để đóng. Đổi root
+     |     là một đợt mới → quota tính lại từ 0.
+     |   - mint_allowlisted: `to` chứng minh (to, allowance) thuộc cây, mint tối đa
+     |     `allowance` NFT trong đợt, trả `price` cho treasury.
+     *-------------------------------------------------------------------------*/
+    pub fn allowlist_set(env: Env, manager: Address, config: Option<AllowlistConfig>) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::Minter, &manager)?;
+        match &config {
+            Some(cfg) => {
+                if cfg.price < 0 { return Err(Error::BadMintConfig); }
+                env.storage().instance().set(&AL_CFG, cfg);
+            }
+            None => env.storage().instance().remove(&AL_CFG),
+        }
+        AllowlistSet { config }.publish(&env);
+        Ok(())
+    }
+
+    pub fn allowlist_get(env: Env) -> Option<AllowlistConfig> {
+        env.storage().instance().get(&AL_CFG)
+    }
+
+    pub fn mint_allowlisted(
+        env: Env,
+        to: Address,
+        pixels: Bytes,
+        allowance: u32,
+        proof: Vec<BytesN<32>>,
+    ) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        to.require_auth();
+        if commit_required(&env) { return Err(Error::CommitRequired); }
+
+        let cfg: AllowlistConfig = env.storage().instance().get(&AL_CFG).ok_or(Error::MintClosed)?;
+        if proof.len() > MAX_PROOF_LEN { return Err(Error::InvalidProof); }
+
+        let mut leaf = to.clone().to_xdr(&env);
+        leaf.extend_from_array(&allowance.to_be_bytes());
+        let mut node: BytesN<32> = env.crypto().sha256(&leaf).into();
+        for sibling in proof.iter() {
+            node = hash_pair(&env, &node, &sibling);
+        }
+        if node != cfg.root { return Err(Error::InvalidProof); }
+
+        let key = (AL_CNT, cfg.root.clone(), to.clone());
+        let minted: u32 = persist_get(&env, &key).unwrap_or(0);
+        if minted >= allowance { return Err(Error::MintLimitReached); }
+
+        let id = mint_nft_internal(&env, to.clone(), pixels)?;
+        if cfg.price > 0 {
+            move_balance(&env, &to, &fee_recipient(&env), cfg.price)?;
+        }
+        persist_set(&env, &key, &(minted + 1));
+        Ok(id)
+    }
+
+    // Số NFT `to` đã mint trong đợt allowlist hiện tại
+    pub fn allowlist_minted_of(env: Env, to: Address) -> u32 {
+        let cfg: Option<AllowlistConfig> = env.storage().instance().get(&AL_CFG);
+        match cfg {
+            Some(c) => persist_get(&env, &(AL_CNT, c.root, to)).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /*-------------------------------------------------------------------------*
+     | nft_index_range (Role::Minter)
+     *-------------------------------------------------------------------------*/
+    pub fn nft_index_range(env: Env, minter: Address, id: u32, start: u32, end: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Minting)?;
+        if end > NFT_PIXELS || start >= end { return Err(Error::BadRange); }
+
+        require_role(&env, Role::Minter, &minter)?;
+
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        let pixels = data.pixels;
+
+        for i in start..end {
+            let col: u32 = pixels.get_unchecked(i).into();
+            let key = (IDX, i, col);
+            let mut vec: Vec<u32> = persist_get(&env, &key).unwrap_or(Vec::new(&env));
+            vec.push_back(id);
+            persist_set(&env, &key, &vec);
+        }
+        mark_indexed(&env, id, start, end);
+
+        NftIndex { id, start, end }.publish(&env);
+        log!(&env, "INDEX_RANGE id={} [{}..{})", id, start, end);
+        Ok(())
+    }
+
+    /*-------------------------------------------------------------------------*
+     | nft_transfer
+     *-------------------------------------------------------------------------*/
+    pub fn nft_transfer(env: Env, from: Address, to: Address, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::NftTransfer)?;
+        from.require_auth();
+        if from == to { return Err(Error::SelfTransfer); }
+        if active_listing(&env, id).is_some() { return Err(Error::Listed); }
+        remove_listing(&env, id);
+        nft_transfer_internal(&env, from, to, id)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Approvals kiểu ERC-721
+     |   - nft_approve: một spender cho một id, hết hạn ở expiration_ledger
+     |     (expiration_ledger = 0 → thu hồi). Tự xoá khi NFT đổi chủ.
+     |   - nft_set_approval_for_all: operator được chuyển mọi NFT của owner.
+     |   - nft_transfer_from: spender là owner, spender của id hoặc operator.
+     *-------------------------------------------------------------------------*/
+    pub fn nft_approve(env: Env, owner: Address, spender: Address, id: u32, expiration_ledger: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::NftTransfer)?;
+        owner.require_auth();
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        if data.owner != owner { return Err(Error::NotOwner); }
+
+        let key = (NFT_APR, id);
+        if expiration_ledger == 0 {
+            env.storage().temporary().remove(&key);
+        } else {
+            let seq = env.ledger().sequence();
+            if expiration_ledger < seq { return Err(Error::BadExpirationLedger); }
+            // extend_ttl vượt max_ttl sẽ trap → báo lỗi trước
+            if expiration_ledger - seq > env.storage().max_ttl() { return Err(Error::BadExpirationLedger); }
+            env.storage().temporary().set(&key, &NftApproval { spender: spender.clone(), expiration_ledger });
+            let live_for = expiration_ledger - seq;
+            env.storage().temporary().extend_ttl(&key, live_for, live_for);
+        }
+        NftApprove { owner, id, spender, expiration_ledger }.publish(&env);
+        Ok(())
+    }
+
+    // None nếu chưa approve hoặc đã hết hạn
+    pub fn nft_get_approved(env: Env, id: u32) -> Option<Address> {
+        read_nft_approval(&env, id)
+    }
+
+    pub fn nft_set_approval_for_all(env: Env, owner: Address, operator: Address, approved: bool) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::NftTransfer)?;
+        owner.require_auth();
+        if owner == operator { return Err(Error::SelfTransfer); }
+        if approved {
+            persist_set(&env, &(NFT_OPR, &owner, &operator), &true);
+        } else {
+            env.storage().persistent().remove(&(NFT_OPR, &owner, &operator));
+        }
+        NftApprovalForAll { owner, operator, approved }.publish(&env);
+        Ok(())
+    }
+
+    pub fn nft_is_approved_for_all(env: Env, owner: Address, operator: Address) -> bool {
+        is_nft_operator(&env, &owner, &operator)
+    }
+
+    pub fn nft_transfer_from(env: Env, spender: Address, from: Address, to: Address, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::NftTransfer)?;
+        spender.require_auth();
+        if from == to { return Err(Error::SelfTransfer); }
+        if active_listing(&env, id).is_some() { return Err(Error::Listed); }
+
+        let approved = spender == from
+            || read_nft_approval(&env, id) == Some(spender.clone())
+            || is_nft_operator(&env, &from, &spender);
+        if !approved { return Err(Error::NotApproved); }
+        remove_listing(&env, id);
+        nft_transfer_internal(&env, from, to, id)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | nft_burn (owner) / nft_admin_burn (ADMIN, kiểm duyệt)
+     |   - Giải phóng pattern (UNIQ) và slot NFT_MAX; id đã burn không cấp lại.
+     |   - Huỷ luôn listing và approval nếu có.
+     *-------------------------------------------------------------------------*/
+    pub fn nft_burn(env: Env, owner: Address, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::NftTransfer)?;
+        owner.require_auth();
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        if data.owner != owner { return Err(Error::NotOwner); }
+        nft_burn_internal(&env, id, data)
+    }
+
+    pub fn nft_admin_burn(env: Env, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        nft_burn_internal(&env, id, data)
+    }
+
+    pub fn nft_total(env: Env) -> u32 {
+        let cur: i128 = env.storage().instance().get(&NFT_SUP).unwrap_or(0);
+        cur as u32
+    }
+
+    pub fn nft_get(env: Env, id: u32) -> Result<(Address, Bytes), Error> {
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        Ok((data.owner, data.pixels))
+    }
+
+    pub fn nft_value(env: Env, id: u32) -> Result<Bytes, Error> {
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        Ok(data.pixels)
+    }
+
+    pub fn nft_find_by_value(env: Env, pixels: Bytes) -> Option<u32> {
+        persist_get(&env, &(UNIQ, &pixels))
+    }
+
+    pub fn nft_search_pos_color(env: Env, pos: u32, color: u32) -> Result<Vec<u32>, Error> {
+        if pos >= NFT_PIXELS { return Err(Error::PosRange0To80); }
+        if color >= 32 { return Err(Error::ColorRange0To31); }
+        let key = (IDX, pos, color);
+        Ok(persist_get(&env, &key).unwrap_or(Vec::new(&env)))
+    }
+
+    pub fn nft_ids_of(env: Env, owner: Address) -> Vec<u32> {
+        persist_get(&env, &(OWN, &owner)).unwrap_or(Vec::new(&env))
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Palette
+     *-------------------------------------------------------------------------*/
+    pub fn palette_get(env: Env) -> Result<Vec<u32>, Error> {
+        env.storage().instance().get(&PAL).ok_or(Error::NoPalette)
+    }
+
+    pub fn palette_set(env: Env, manager: Address, new_palette: Vec<u32>) -> Result<(), Error> {
+        require_inited(&env)?;
+        if new_palette.len() != 32 { return Err(Error::Palette32Required); }
+        require_role(&env, Role::PaletteManager, &manager)?;
+        require_no_council(&env)?;
+        if timelock_delay(&env) > 0 { return Err(Error::TimelockRequired); }
+        write_palette(&env, new_palette);
+        Ok(())
+    }
+
+
+    // Marketplace
+    pub fn listing_fee_set(env: Env, manager: Address, fee: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        if fee < 0 { return Err(Error::BadFee); }
+        require_role(&env, Role::FeeManager, &manager)?;
+        require_no_council(&env)?;
+        if timelock_delay(&env) > 0 { return Err(Error::TimelockRequired); }
+        write_listing_fee(&env, fee);
+        Ok(())
+    }
+
+    pub fn listing_fee_get(env: Env) -> i128 {
+        env.storage().instance().get::<Symbol, i128>(&LSTFEE).unwrap_or(0)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Timelock cho LSTFEE, TFEE, SFEE & palette
+     |   - timelock_delay_set (ADMIN): bật (delay > 0) / tăng độ trễ ngay;
+     |     giảm hoặc tắt phải qua TimelockChange::Delay.
+     |   - Khi bật: listing_fee_set / transfer_fee_set / sale_fee_set / palette_set
+     |     trả TimelockRequired, dùng timelock_schedule (cùng vai trò) → chờ ≥ delay
+     |     ledger → timelock_execute
+     |     (ai cũng gọi được). Người có vai trò có thể timelock_cancel trước đó.
+     |   - Council thực thi PaletteSet / ListingFeeSet cũng được xếp hàng; khi có
+     |     council, huỷ LSTFEE / palette / Delay chỉ qua AdminAction::TimelockCancel.
+     *-------------------------------------------------------------------------*/
+    pub fn timelock_delay_set(env: Env, delay: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        if delay > TL_MAX_DELAY { return Err(Error::BadTimelockDelay); }
+        let old_delay = timelock_delay(&env);
+        if delay < old_delay { return Err(Error::TimelockRequired); }
+        write_timelock_delay(&env, delay);
+        Ok(())
+    }
+
+    pub fn timelock_delay(env: Env) -> u32 {
+        timelock_delay(&env)
+    }
+
+    // Trả về id thay đổi; có hiệu lực từ ledger hiện tại + delay
+    pub fn timelock_schedule(env: Env, caller: Address, change: TimelockChange) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_change_auth(&env, &change, &caller)?;
+        validate_change(&change)?;
+        schedule_change(&env, caller, change)
+    }
+
+    pub fn timelock_cancel(env: Env, caller: Address, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let item: ScheduledChange = persist_get(&env, &(TL_Q, id)).ok_or(Error::TimelockNotFound)?;
+        require_change_auth(&env, &item.change, &caller)?;
+        remove_scheduled(&env, id);
+        TimelockCancelled { id }.publish(&env);
+        Ok(())
+    }
+
+    pub fn timelock_execute(env: Env, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        let item: ScheduledChange = persist_get(&env, &(TL_Q, id)).ok_or(Error::TimelockNotFound)?;
+        if env.ledger().sequence() < item.eta_ledger { return Err(Error::TimelockNotReady); }
+        remove_scheduled(&env, id);
+        match item.change.clone() {
+            TimelockChange::ListingFee(fee) => write_listing_fee(&env, fee),
+            TimelockChange::Palette(palette) => write_palette(&env, palette),
+            TimelockChange::Delay(delay) => write_timelock_delay(&env, delay),
+            TimelockChange::TransferFee(config) => write_transfer_fee(&env, config),
+            TimelockChange::SaleFee(bps) => write_sale_fee(&env, bps),
+        }
+        TimelockExecuted { id, change: item.change }.publish(&env);
+        Ok(())
+    }
+
+    pub fn timelock_get(env: Env, id: u32) -> Option<ScheduledChange> {
+        persist_get(&env, &(TL_Q, id))
+    }
+
+    // Các thay đổi đang chờ (theo thứ tự xếp hàng) để dApp hiển thị
+    pub fn timelock_pending(env: Env) -> Vec<ScheduledChange> {
+        let ids: Vec<u32> = persist_get(&env, &TL_IDS).unwrap_or(Vec::new(&env));
+        let mut out = Vec::new(&env);
+        for id in ids.iter() {
+            if let Some(item) = persist_get::<_, ScheduledChange>(&env, &(TL_Q, id)) {
+                out.push_back(item);
+            }
+        }
+        out
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Transfer fee (Role::FeeManager) & treasury (ADMIN)
+     |   - mode Flat: value = phí raw / giao dịch; Bps: value = 0..10_000.
+     |   - min_fee / max_fee (tuỳ chọn) kẹp phí sau khi tính.
+     |   - Mọi phí (transfer, listing) cộng cho treasury; chưa đặt → ADMIN.
+     |   - Người trả phí nằm trong danh sách FEE_EXEMPT thì không bị tính phí.
+     |   - Khi bật timelock: đổi qua TimelockChange::TransferFee.
+     *-------------------------------------------------------------------------*/
+    pub fn transfer_fee_set(
+        env: Env,
+        manager: Address,
+        mode: FeeMode,
+        value: i128,
+        min_fee: Option<i128>,
+        max_fee: Option<i128>,
+    ) -> Result<(), Error> {
+        require_inited(&env)?;
+        let config = TransferFeeConfig { mode, value, min_fee, max_fee };
+        validate_fee_config(&config)?;
+        require_role(&env, Role::FeeManager, &manager)?;
+        if timelock_delay(&env) > 0 { return Err(Error::TimelockRequired); }
+        write_transfer_fee(&env, config);
+        Ok(())
+    }
+
+    pub fn transfer_fee_get(env: Env) -> TransferFeeConfig {
+        read_fee_config(&env)
+    }
+
+    // Phí sẽ bị trừ thêm khi transfer `amount` (dApp hiển thị trước khi ký)
+    pub fn transfer_fee_quote(env: Env, amount: i128) -> Result<i128, Error> {
+        if amount <= 0 { return Err(Error::BadAmount); }
+        quote_fee(&read_fee_config(&env), amount)
+    }
+
+    pub fn treasury_set(env: Env, treasury: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+        admin.require_auth();
+        require_no_council(&env)?;
+        write_treasury(&env, treasury);
+        Ok(())
+    }
+
+    pub fn treasury_get(env: Env) -> Result<Address, Error> {
+        require_inited(&env)?;
+        Ok(fee_recipient(&env))
+    }
+
+    // Miễn phí transfer / listing cho `account` (khi account là người trả phí)
+    pub fn fee_exempt_set(env: Env, manager: Address, account: Address, exempt: bool) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::FeeManager, &manager)?;
+        if exempt {
+            persist_set(&env, &(FEE_EXEMPT, &account), &true);
+        } else {
+            env.storage().persistent().remove(&(FEE_EXEMPT, &account));
+        }
+        FeeExemptSet { account, exempt }.publish(&env);
+        Ok(())
+    }
+
+    pub fn fee_exempt_get(env: Env, account: Address) -> bool {
+        is_fee_exempt(&env, &account)
+    }
+
+    // expires_ledger: listing hết hiệu lực sau ledger này (None → không hết hạn)
+    pub fn market_list_nft(env: Env, seller: Address, id: u32, price: i128, expires_ledger: Option<u32>) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Market)?;
+        seller.require_auth();
+        if price <= 0 { return Err(Error::BadPrice); }
+        if let Some(exp) = expires_ledger {
+            if exp < env.ledger().sequence() { return Err(Error::BadExpirationLedger); }
+        }
+
+        let data: NftData = persist_get(&env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+        if data.owner != seller { return Err(Error::NotOwner); }
+
+        // Listing cũ đã hết hạn thì thay thế
+        if active_listing(&env, id).is_some() { return Err(Error::AlreadyListed); }
+        remove_listing(&env, id);
+
+        let fee: i128 = if is_fee_exempt(&env, &seller) { 0 } else { env.storage().instance().get(&LSTFEE).unwrap_or(0) };
+        if fee > 0 {
+            let treasury = fee_recipient(&env);
+            let mut seller_bal: i128 = persist_get(&env, &(BAL, &seller)).unwrap_or(0);
+            if seller_bal < fee { return Err(Error::InsufficientForFee); }
+            seller_bal -= fee;
+            persist_set(&env, &(BAL, &seller), &seller_bal);
+
+            let treasury_bal: i128 = persist_get(&env, &(BAL, &treasury)).unwrap_or(0);
+            persist_set(&env, &(BAL, &treasury), &(treasury_bal.checked_add(fee).ok_or(Error::BalOverflow)?));
+            Transfer { from: seller.clone(), to: treasury, amount: fee }.publish(&env);
+        }
+
+        let lst = Listing { seller: seller.clone(), price, expires_ledger };
+        persist_set(&env, &(LST, id), &lst);
+        persist_set(&env, &(LST_UPD, id), &env.ledger().sequence());
+        bump_listing_nonce(&env, id);
+
+        let mut ids: Vec<u32> = persist_get(&env, &LIDS).unwrap_or(Vec::new(&env));
+        ids.push_back(id);
+        persist_set(&env, &LIDS, &ids);
+
+        MarketList { id, seller: seller.clone(), price, fee }.publish(&env);
+        log!(&env, "LIST id={} seller={} price={}", id, seller, price);
+        Ok(())
+    }
+
+    // Đổi giá tại chỗ (giữ vị trí trong LIDS, không thu lại LSTFEE).
+    // Tăng giá phải cách lần đặt giá trước ≥ LCD ledger; giảm giá thì không giới hạn.
+    pub fn market_update_price(env: Env, seller: Address, id: u32, new_price: i128) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Market)?;
+        seller.require_auth();
+        if new_price <= 0 { return Err(Error::BadPrice); }
+
+        let mut lst: Listing = persist_get(&env, &(LST, id)).ok_or(Error::NotListed)?;
+        if lst.seller != seller { return Err(Error::NotSeller); }
+        if listing_expired(&env, &lst) { return Err(Error::ListingExpired); }
+
+        let seq = env.ledger().sequence();
+        if new_price > lst.price {
+            let cooldown: u32 = env.storage().instance().get(&LST_CD).unwrap_or(0);
+            let last: u32 = persist_get(&env, &(LST_UPD, id)).unwrap_or(0);
+            if seq < last.saturating_add(cooldown) { return Err(Error::PriceCooldown); }
+        }
+
+        let old_price = lst.price;
+        lst.price = new_price;
+        persist_set(&env, &(LST, id), &lst);
+        persist_set(&env, &(LST_UPD, id), &seq);
+        bump_listing_nonce(&env, id);
+        MarketPriceChanged { id, seller, old_price, new_price }.publish(&env);
+        Ok(())
+    }
+
+    pub fn market_price_cooldown_set(env: Env, manager: Address, ledgers: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_role(&env, Role::FeeManager, &manager)?;
+        let old: u32 = env.storage().instance().get(&LST_CD).unwrap_or(0);
+        env.storage().instance().set(&LST_CD, &ledgers);
+        PriceCooldownSet { old, new: ledgers }.publish(&env);
+        Ok(())
+    }
+
+    pub fn market_price_cooldown(env: Env) -> u32 {
+        env.storage().instance().get(&LST_CD).unwrap_or(0)
+    }
+
+    pub fn market_cancel(env: Env, seller: Address, id: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Market)?;
+        seller.require_auth();
+
+        let lst: Listing = persist_get(&env, &(LST, id)).ok_or(Error::NotListed)?;
+        if lst.seller != seller { return Err(Error::NotSeller); }
+
+        remove_listing(&env, id);
+
+        MarketCancel { id, seller: seller.clone() }.publish(&env);
+        log!(&env, "UNLIST id={} seller={}", id, seller);
+        Ok(())
+    }
+
+    pub fn market_buy(env: Env, buyer: Address, id: u32) -> Result<(), Error> {
+        buy_listing(&env, buyer, id, None, None)
+    }
+
+    // Như market_buy nhưng chỉ khớp khi giá ≤ max_price và (nếu có) nonce đúng listing đã xem
+    pub fn market_buy_checked(env: Env, buyer: Address, id: u32, max_price: i128, nonce: Option<u32>) -> Result<(), Error> {
+        buy_listing(&env, buyer, id, Some(max_price), nonce)
+    }
+
+    // Nonce hiện tại của listing `id` (dùng cho market_buy_checked)
+    pub fn market_listing_nonce(env: Env, id: u32) -> u32 {
+        persist_get(&env, &(LST_NON, id)).unwrap_or(0)
+    }
+
+    pub fn market_quote_buy(env: Env, id: u32) -> Result<MarketQuote, Error> {
+        let lst: Listing = persist_get(&env, &(LST, id)).ok_or(Error::NotListed)?;
+        if listing_expired(&env, &lst) { return Err(Error::ListingExpired); }
+        quote_sale(&env, id, &lst)
+    }
+
+    // Phí giao dịch (Role::FeeManager), bps trên giá bán, seller chịu; có timelock → TimelockChange::SaleFee
+    pub fn sale_fee_set(env: Env, manager: Address, bps: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        if bps > MAX_SALE_FEE_BPS { return Err(Error::BadFee); }
+        require_role(&env, Role::FeeManager, &manager)?;
+        if timelock_delay(&env) > 0 { return Err(Error::TimelockRequired); }
+        write_sale_fee(&env, bps);
+        Ok(())
+    }
+
+    pub fn sale_fee_get(env: Env) -> u32 {
+        env.storage().instance().get(&SALE_FEE).unwrap_or(0)
+    }
+
+    /*-------------------------------------------------------------------------*
+     | Royalty (kiểu EIP-2981)
+     |   - Mỗi NFT mint mới ghi creator = người nhận NFT và bps = ROYB lúc mint.
+     |   - market_buy trích royalty từ giá bán cho recipient.
+     *-------------------------------------------------------------------------*/
+    pub fn royalty_default_set(env: Env, manager: Address, bps: u32) -> Result<(), Error> {
+        require_inited(&env)?;
+        if bps > MAX_ROYALTY_BPS { return Err(Error::BadRoyalty); }
+        require_role(&env, Role::FeeManager, &manager)?;
+        env.storage().instance().set(&ROY_BPS, &bps);
+        RoyaltyDefaultSet { bps }.publish(&env);
+        Ok(())
+    }
+
+    pub fn royalty_default_get(env: Env) -> u32 {
+        env.storage().instance().get(&ROY_BPS).unwrap_or(0)
+    }
+
+    pub fn royalty_get(env: Env, id: u32) -> Option<RoyaltyInfo> {
+        persist_get(&env, &(ROY, id))
+    }
+
+    // (recipient, amount) cho giá bán `sale_price`; None nếu NFT không có royalty
+    pub fn royalty_info(env: Env, id: u32, sale_price: i128) -> Result<Option<(Address, i128)>, Error> {
+        if sale_price < 0 { return Err(Error::BadPrice); }
+        royalty_for(&env, id, sale_price)
+    }
+
+    pub fn royalty_recipient_set(env: Env, creator: Address, id: u32, recipient: Address) -> Result<(), Error> {
+        require_inited(&env)?;
+        creator.require_auth();
+        let mut info: RoyaltyInfo = persist_get(&env, &(ROY, id)).ok_or(Error::NftNotFound)?;
+        if info.creator != creator { return Err(Error::NotCreator); }
+        info.recipient = recipient.clone();
+        persist_set(&env, &(ROY, id), &info);
+        RoyaltyRecipientSet { id, creator, recipient }.publish(&env);
+        Ok(())
+    }
+
+    // Listing đã hết hạn được xem như không niêm yết
+    pub fn market_get(env: Env, id: u32) -> Option<(Address, i128)> {
+        active_listing(&env, id).map(|l| (l.seller, l.price))
+    }
+
+    pub fn market_listing(env: Env, id: u32) -> Option<Listing> {
+        active_listing(&env, id)
+    }
+
+    // Số phần tử trong LIDS (1 lần đọc), có thể gồm listing hết hạn chưa prune;
+    // dùng làm giới hạn khi phân trang market_list_ids_page
+    pub fn market_list_count(env: Env) -> u32 {
+        persist_get::<_, Vec<u32>>(&env, &LIDS).map(|v| v.len()).unwrap_or(0)
+    }
+
+    // Các listing còn hiệu lực trong LIDS[start, start + limit) — đọc tối đa `limit` listing
+    pub fn market_list_ids_page(env: Env, start: u32, limit: u32) -> Vec<u32> {
+        let ids: Vec<u32> = persist_get(&env, &LIDS).unwrap_or(Vec::new(&env));
+        let end = start.saturating_add(limit).min(ids.len());
+        let mut out = Vec::new(&env);
+        for i in start..end {
+            let id = ids.get_unchecked(i);
+            if active_listing(&env, id).is_some() { out.push_back(id); }
+        }
+        out
+    }
+
+    // Ai cũng gọi được: quét tối đa `max` listing từ con trỏ LST_PRC và xoá listing đã hết hạn
+    // khỏi LST/LIDS; con trỏ quay về 0 khi quét hết LIDS. Trả về số đã xoá
+    pub fn market_prune_expired(env: Env, max: u32) -> Result<u32, Error> {
+        require_inited(&env)?;
+        require_not_paused(&env, Feature::Market)?;
+        let ids: Vec<u32> = persist_get(&env, &LIDS).unwrap_or(Vec::new(&env));
+        let mut start: u32 = env.storage().instance().get(&LST_PRC).unwrap_or(0);
+        if start >= ids.len() { start = 0; }
+        let end = start.saturating_add(max).min(ids.len());
+        let mut keep = Vec::new(&env);
+        let mut removed: u32 = 0;
+        for i in 0..ids.len() {
+            let id = ids.get_unchecked(i);
+            if i >= start && i < end {
+                if let Some(lst) = persist_get::<_, Listing>(&env, &(LST, id)) {
+                    if listing_expired(&env, &lst) {
+                        env.storage().persistent().remove(&(LST, id));
+                        env.storage().persistent().remove(&(LST_UPD, id));
+                        MarketPruned { id, seller: lst.seller }.publish(&env);
+                        removed += 1;
+                        continue;
+                    }
+                }
+            }
+            keep.push_back(id);
+        }
+        let next = end - removed;
+        env.storage().instance().set(&LST_PRC, &if next >= keep.len() { 0 } else { next });
+        if removed > 0 { persist_set(&env, &LIDS, &keep); }
+        Ok(removed)
+    }
+
+    // | Token read tiện ích
+    pub fn name(env: Env) -> Result<String, Error> {
+        env.storage().instance().get::<Symbol, String>(&NAME).ok_or(Error::NotInited)
+    }
+    pub fn symbol(env: Env) -> Result<String, Error> {
+        env.storage().instance().get::<Symbol, String>(&SYMBOL_).ok_or(Error::NotInited)
+    }
+    pub fn decimals(env: Env) -> Result<u32, Error> {
+        env.storage().instance().get::<Symbol, u32>(&DECIMALS).ok_or(Error::NotInited)
+    }
+    pub fn total_supply(env: Env) -> Result<i128, Error> {
+        env.storage().instance().get::<Symbol, i128>(&SUPPLY).ok_or(Error::NotInited)
+    }
+    pub fn max_supply(env: Env) -> Option<i128> {
+        env.storage().instance().get::<Symbol, i128>(&MAX_SUP)
+    }
+    pub fn balance_of(env: Env, of: Address) -> i128 {
+        persist_get(&env, &(BAL, &of)).unwrap_or(0)
+    }
+    // Tên chuẩn SEP-41 của balance_of
+    pub fn balance(env: Env, id: Address) -> i128 {
+        persist_get(&env, &(BAL, &id)).unwrap_or(0)
+    }
+}
+
+
+fn require_inited(env: &Env) -> Result<(), Error> {
+    if !env.storage().instance().has(&INITED) { return Err(Error::NotInited); }
+    if read_version(env) != SCHEMA_VERSION { return Err(Error::MigrationRequired); }
+    bump_instance(env);
+    Ok(())
+}
+
+fn ttl_config(env: &Env) -> TtlConfig {
+    env.storage().instance().get(&TTL_CFG).unwrap_or(TtlConfig {
+        instance_threshold: INSTANCE_TTL_THRESHOLD,
+        instance_extend_to: INSTANCE_TTL_EXTEND_TO,
+        persistent_threshold: PERSISTENT_TTL_THRESHOLD,
+        persistent_extend_to: PERSISTENT_TTL_EXTEND_TO,
+    })
+}
+
+fn bump_instance(env: &Env) {
+    let cfg = ttl_config(env);
+    env.storage().instance().extend_ttl(cfg.instance_threshold, cfg.instance_extend_to);
+}
+
+// Đọc persistent, gia hạn TTL nếu entry tồn tại
+fn persist_get<K, V>(env: &Env, key: &K) -> Option<V>
+where
+    K: IntoVal<Env, Val>,
+    V: TryFromVal<Env, Val>,
+{
+    let v: Option<V> = env.storage().persistent().get(key);
+    if v.is_some() {
+        let cfg = ttl_config(env);
+        env.storage().persistent().extend_ttl(key, cfg.persistent_threshold, cfg.persistent_extend_to);
+    }
+    v
+}
+
+// Ghi persistent và gia hạn TTL
+fn persist_set<K, V>(env: &Env, key: &K, val: &V)
+where
+    K: IntoVal<Env, Val>,
+    V: IntoVal<Env, Val>,
+{
+    env.storage().persistent().set(key, val);
+    let cfg = ttl_config(env);
+    env.storage().persistent().extend_ttl(key, cfg.persistent_threshold, cfg.persistent_extend_to);
+}
+
+// Gia hạn đủ extend_to (bỏ qua threshold) cho entry đang tồn tại — dùng cho bump_*
+fn persist_bump<K: IntoVal<Env, Val>>(env: &Env, key: &K, extend_to: u32) -> bool {
+    if !env.storage().persistent().has(key) { return false; }
+    env.storage().persistent().extend_ttl(key, extend_to, extend_to);
+    true
+}
+
+fn read_version(env: &Env) -> u32 {
+    env.storage().instance().get(&VERSION).unwrap_or(1)
+}
+
+// Chuyển storage từ schema `from` lên `from + 1`
+fn migrate_step(env: &Env, from: u32) {
+    if from == 1 {
+        // v1 → v2: LIDS instance → persistent
+        let ids: Vec<u32> = env.storage().instance().get(&LIDS).unwrap_or(Vec::new(env));
+        persist_set(env, &LIDS, &ids);
+        env.storage().instance().remove(&LIDS);
+    }
+    if from == 2 {
+        // v2 → v3: Listing thêm expires_ledger = None
+        let ids: Vec<u32> = persist_get(env, &LIDS).unwrap_or(Vec::new(env));
+        for id in ids.iter() {
+            if let Some(old) = persist_get::<_, ListingV2>(env, &(LST, id)) {
+                let lst = Listing { seller: old.seller, price: old.price, expires_ledger: None };
+                persist_set(env, &(LST, id), &lst);
+            }
+        }
+    }
+    if from == 3 {
+        // v3 → v4: NFT đã mint chưa có IDX_MASK → ghi mốc id để burn quét đủ 81 vị trí
+        let next: i128 = env.storage().instance().get(&NEXT_ID).unwrap_or(0);
+        env.storage().instance().set(&IDX_OLD, &(next as u32));
+    }
+}
+
+fn is_paused(env: &Env, feature: Feature) -> bool {
+    let flag = |f: Feature| env.storage().instance().get(&(PAUSE, f)).unwrap_or(false);
+    flag(Feature::All) || flag(feature)
 }
 
-#[contracttype]
-#[derive(Clone)]
-pub struct Listing {
-    pub seller: Address,
-    pub price: i128,
+fn require_not_paused(env: &Env, feature: Feature) -> Result<(), Error> {
+    if is_paused(env, feature) { return Err(Error::Paused); }
+    Ok(())
 }
 
+fn has_role(env: &Env, role: Role, account: &Address) -> bool {
+    persist_get(env, &(ROLE, role, account)).unwrap_or(false)
+}
 
-#[contract]
-pub struct SimpleTokenNft;
+// caller phải ký và là ADMIN hoặc có `role`
+// ADMIN mặc nhiên có mọi vai trò, trừ khi đã có council
+fn require_role(env: &Env, role: Role, caller: &Address) -> Result<(), Error> {
+    caller.require_auth();
+    if has_role(env, role, caller) { return Ok(()); }
+    let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+    if *caller == admin && !env.storage().instance().has(&COUNCIL) { return Ok(()); }
+    Err(Error::MissingRole)
+}
 
-#[contractimpl]
-impl SimpleTokenNft {
+// Chuyển FT from → to, from trả thêm phí theo TransferFeeConfig, phí cộng cho treasury. Trả về phí.
+fn transfer_with_fee(env: &Env, from: &Address, to: &Address, amount: i128) -> Result<i128, Error> {
+    let from_bal: i128 = persist_get(env, &(BAL, from)).unwrap_or(0);
+    let to_bal: i128 = persist_get(env, &(BAL, to)).unwrap_or(0);
+    let treasury = fee_recipient(env);
 
-    // init
-    pub fn init(env: Env, admin: Address, name: String, symbol: String, decimals: u32) {
-        if env.storage().instance().has(&INITED) { panic!("ALREADY_INIT"); }
-        admin.require_auth();
+    let fee: i128 = transfer_fee_for(env, from, amount)?;
+    let total_deduct = amount.checked_add(fee).ok_or(Error::FeeAddOverflow)?;
+    if from_bal < total_deduct { return Err(Error::InsufficientBalanceWithFee); }
 
-        // Bắt buộc 3 chữ số thập phân
-        if decimals != 3 { panic!("DECIMALS_MUST_BE_3"); }
+    // Cập nhật to
+    let new_to   = to_bal.checked_add(amount).ok_or(Error::BalOverflow)?;
+    persist_set(env, &(BAL, to),   &new_to);
 
-        // Tổng cung = 100_000_000_000 * 10^3 = 100_000_000_000_000 (raw)
-        let total: i128 = mul_pow10_i128(100_000_000_000i128, decimals).expect("SUPPLY_OVERFLOW");
+    // Khấu trừ from
+    let new_from = from_bal - total_deduct;
+    persist_set(env, &(BAL, from), &new_from);
 
-        // Lưu metadata token
-        env.storage().instance().set(&ADMIN, &admin);
-        env.storage().instance().set(&NAME, &name);
-        env.storage().instance().set(&SYMBOL_, &symbol);
-        env.storage().instance().set(&DECIMALS, &decimals);
-        env.storage().instance().set(&SUPPLY, &total);
-        env.storage().instance().set(&INITED, &true);
+    Transfer { from: from.clone(), to: to.clone(), amount }.publish(env);
 
-        // Cấp FT cho admin
-        env.storage().persistent().set(&(BAL, &admin), &total);
+    // Luôn cộng phí cho treasury
+    if fee > 0 {
+        let treasury_bal: i128 = persist_get(env, &(BAL, &treasury)).unwrap_or(0);
+        let new_treasury = treasury_bal.checked_add(fee).ok_or(Error::BalOverflow)?;
+        persist_set(env, &(BAL, &treasury), &new_treasury);
+        Transfer { from: from.clone(), to: treasury, amount: fee }.publish(env);
+    }
+    Ok(fee)
+}
 
-        // Palette & marketplace defaults
-        env.storage().instance().set(&PAL, &default_palette(&env));
-        env.storage().instance().set(&NFT_SUP, &0i128);
-        env.storage().instance().set(&NEXT_ID, &0i128);
-        env.storage().instance().set(&LSTFEE, &1i128); // 0.001 token (raw)
-        let empty_ids: Vec<u32> = Vec::new(&env);
-        env.storage().instance().set(&LIDS, &empty_ids);
+// Phí transfer mà `from` phải trả cho amount (0 nếu from được miễn phí)
+fn transfer_fee_for(env: &Env, from: &Address, amount: i128) -> Result<i128, Error> {
+    if is_fee_exempt(env, from) { return Ok(0); }
+    quote_fee(&read_fee_config(env), amount)
+}
 
-        log!(&env, "INIT OK supply={} (decimals=3) transfer_fee=0.001 listing_fee=0.001", total);
+fn is_fee_exempt(env: &Env, account: &Address) -> bool {
+    persist_get(env, &(FEE_EXEMPT, account)).unwrap_or(false)
+}
+
+fn validate_fee_config(config: &TransferFeeConfig) -> Result<(), Error> {
+    if config.value < 0 { return Err(Error::BadFeeConfig); }
+    if config.mode == FeeMode::Bps && config.value > BPS_DENOM { return Err(Error::BadFeeConfig); }
+    if config.min_fee.is_some_and(|m| m < 0) || config.max_fee.is_some_and(|m| m < 0) {
+        return Err(Error::BadFeeConfig);
     }
+    if let (Some(min), Some(max)) = (config.min_fee, config.max_fee) {
+        if min > max { return Err(Error::BadFeeConfig); }
+    }
+    Ok(())
+}
 
-    // transfer (FT) — người gửi trả phí 0.001 token (1 raw)
-    pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
-        require_inited(&env);
-        if amount <= 0 { panic!("BAD_AMOUNT"); }
-        from.require_auth();
-        if from == to { panic!("SELF_TRANSFER"); }
+fn write_transfer_fee(env: &Env, config: TransferFeeConfig) {
+    env.storage().instance().set(&TFEE, &config);
+    TransferFeeSet { config }.publish(env);
+}
 
-        let from_bal: i128 = env.storage().persistent().get(&(BAL, &from)).unwrap_or(0);
-        let to_bal: i128 = env.storage().persistent().get(&(BAL, &to)).unwrap_or(0);
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+fn write_sale_fee(env: &Env, bps: u32) {
+    let old_bps: u32 = env.storage().instance().get(&SALE_FEE).unwrap_or(0);
+    env.storage().instance().set(&SALE_FEE, &bps);
+    SaleFeeSet { old_bps, new_bps: bps }.publish(env);
+}
 
-        let fee: i128 = TRANSFER_FEE_RAW;
-        let total_deduct = amount.checked_add(fee).expect("FEE_ADD_OVERFLOW");
-        if from_bal < total_deduct { panic!("INSUFFICIENT_BALANCE_WITH_FEE"); }
+fn read_fee_config(env: &Env) -> TransferFeeConfig {
+    env.storage().instance().get(&TFEE).unwrap_or(TransferFeeConfig {
+        mode: FeeMode::Flat,
+        value: TRANSFER_FEE_RAW,
+        min_fee: None,
+        max_fee: None,
+    })
+}
 
-        // Cập nhật to
-        let new_to   = to_bal.checked_add(amount).expect("BAL_OVERFLOW");
-        env.storage().persistent().set(&(BAL, &to),   &new_to);
+// Phí cho một giao dịch amount: Flat/Bps, sau đó kẹp vào [min_fee, max_fee]
+fn quote_fee(cfg: &TransferFeeConfig, amount: i128) -> Result<i128, Error> {
+    let mut fee = match cfg.mode {
+        FeeMode::Flat => cfg.value,
+        FeeMode::Bps => amount.checked_mul(cfg.value).ok_or(Error::FeeMulOverflow)? / BPS_DENOM,
+    };
+    if let Some(min) = cfg.min_fee { if fee < min { fee = min; } }
+    if let Some(max) = cfg.max_fee { if fee > max { fee = max; } }
+    Ok(fee)
+}
 
-        // Khấu trừ from
-        let new_from = from_bal - total_deduct;
-        env.storage().persistent().set(&(BAL, &from), &new_from);
+// Người nhận phí: TREASURY nếu đã cấu hình, ngược lại là ADMIN
+fn fee_recipient(env: &Env) -> Address {
+    env.storage().instance().get(&TREASURY)
+        .unwrap_or_else(|| env.storage().instance().get(&ADMIN).unwrap())
+}
 
-        // Luôn cộng phí cho admin
-        let admin_bal: i128 = env.storage().persistent().get(&(BAL, &admin)).unwrap_or(0);
-        let new_admin = admin_bal.checked_add(fee).expect("BAL_OVERFLOW");
-        env.storage().persistent().set(&(BAL, &admin), &new_admin);
+// Chuyển FT from → to không tính phí transfer (thanh toán trong contract)
+fn move_balance(env: &Env, from: &Address, to: &Address, amount: i128) -> Result<(), Error> {
+    let from_bal: i128 = persist_get(env, &(BAL, from)).unwrap_or(0);
+    if from_bal < amount { return Err(Error::InsufficientBalance); }
+    if from == to { return Ok(()); }
+
+    let to_bal: i128 = persist_get(env, &(BAL, to)).unwrap_or(0);
+    persist_set(env, &(BAL, to), &to_bal.checked_add(amount).ok_or(Error::BalOverflow)?);
+    persist_set(env, &(BAL, from), &(from_bal - amount));
+    Transfer { from: from.clone(), to: to.clone(), amount }.publish(env);
+    Ok(())
+}
 
-        log!(&env, "TRANSFER: {} -> {} amount={} fee_raw={}", from, to, amount, fee);
-    }
+// sha256(min(a, b) ‖ max(a, b))
+fn hash_pair(env: &Env, a: &BytesN<32>, b: &BytesN<32>) -> BytesN<32> {
+    let (lo, hi) = if a.to_array() <= b.to_array() { (a, b) } else { (b, a) };
+    let mut buf = Bytes::from_array(env, &lo.to_array());
+    buf.extend_from_array(&hi.to_array());
+    env.crypto().sha256(&buf).into()
+}
 
-    // -------------------------------------------------------------------------
-    // transfer_batch
-    //   - Gửi token tới nhiều địa chỉ trong 1 lần gọi.
-    //   - Người gửi trả phí cố định 1 raw / giao dịch.
-    //   - Tổng khấu trừ = sum(amounts) + len(tos)*fee_raw.
-    //   - Luôn cộng tổng phí cho admin để bảo toàn tổng cung.
-    //   - Trả về tổng phí đã thu (raw).
-    // -------------------------------------------------------------------------
-    pub fn transfer_batch(env: Env, from: Address, tos: Vec<Address>, amounts: Vec<i128>) -> i128 {
-        require_inited(&env);
-        from.require_auth();
+// Đang mở bán + trong cửa sổ + chưa vượt giới hạn ví → (config, số đã mint)
+fn check_public_mint(env: &Env, creator: &Address) -> Result<(PublicMintConfig, u32), Error> {
+    let cfg = read_public_mint(env);
+    if !cfg.enabled { return Err(Error::MintClosed); }
+    let seq = env.ledger().sequence();
+    if seq < cfg.start_ledger { return Err(Error::MintNotStarted); }
+    if cfg.end_ledger != 0 && seq > cfg.end_ledger { return Err(Error::MintEnded); }
+
+    let minted: u32 = persist_get(env, &(PM_CNT, creator)).unwrap_or(0);
+    if cfg.wallet_limit != 0 && minted >= cfg.wallet_limit { return Err(Error::MintLimitReached); }
+    Ok((cfg, minted))
+}
 
-        let n = tos.len();
-        if n == 0 { panic!("EMPTY_BATCH"); }
-        if n != amounts.len() { panic!("LENGTH_MISMATCH"); }
+// Không có CMT_REQ → bắt buộc commit-reveal
+fn commit_required(env: &Env) -> bool {
+    env.storage().instance().get(&CMT_REQ).unwrap_or(true)
+}
 
-        // Validate inputs & tính tổng amount
-        let mut sum_amounts: i128 = 0;
-        for i in 0..n {
-            let to = tos.get_unchecked(i);
-            let amt = amounts.get_unchecked(i);
-            if amt <= 0 { panic!("BAD_AMOUNT_AT"); }
-            if to == from { panic!("SELF_TRANSFER_AT"); }
-            sum_amounts = sum_amounts.checked_add(amt).expect("AMOUNT_SUM_OVERFLOW");
-        }
+fn commit_hash(env: &Env, creator: &Address, pixels: &Bytes, salt: &BytesN<32>) -> BytesN<32> {
+    let mut buf = creator.clone().to_xdr(env);
+    buf.append(pixels);
+    buf.extend_from_array(&salt.to_array());
+    env.crypto().sha256(&buf).into()
+}
 
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-        let fee_per: i128 = TRANSFER_FEE_RAW;
-        let n_i128: i128 = n as i128;
-        let total_fee: i128 = fee_per.checked_mul(n_i128).expect("FEE_MUL_OVERFLOW");
-        let total_deduct: i128 = sum_amounts.checked_add(total_fee).expect("TOTAL_DEDUCT_OVERFLOW");
+// Royalty của NFT `id` cho giá `price`; None nếu không có hoặc bằng 0
+fn royalty_for(env: &Env, id: u32, price: i128) -> Result<Option<(Address, i128)>, Error> {
+    let info: Option<RoyaltyInfo> = persist_get(env, &(ROY, id));
+    let Some(info) = info else { return Ok(None) };
+    let amount = price.checked_mul(i128::from(info.bps)).ok_or(Error::FeeMulOverflow)? / BPS_DENOM;
+    if amount == 0 { return Ok(None); }
+    Ok(Some((info.recipient, amount)))
+}
 
-        // Kiểm tra số dư người gửi
-        let from_bal: i128 = env.storage().persistent().get(&(BAL, &from)).unwrap_or(0);
-        if from_bal < total_deduct { panic!("INSUFFICIENT_BALANCE_WITH_FEE"); }
+fn buy_listing(env: &Env, buyer: Address, id: u32, max_price: Option<i128>, nonce: Option<u32>) -> Result<(), Error> {
+    require_inited(env)?;
+    require_not_paused(env, Feature::Market)?;
+    buyer.require_auth();
 
-        // Cộng cho từng người nhận
-        for i in 0..n {
-            let to = tos.get_unchecked(i);
-            let amt = amounts.get_unchecked(i);
-            let to_bal: i128 = env.storage().persistent().get(&(BAL, &to)).unwrap_or(0);
-            let new_to = to_bal.checked_add(amt).expect("BAL_OVERFLOW");
-            env.storage().persistent().set(&(BAL, &to), &new_to);
-        }
+    let lst: Listing = persist_get(env, &(LST, id)).ok_or(Error::NotListed)?;
+    if listing_expired(env, &lst) { return Err(Error::ListingExpired); }
+    if let Some(max) = max_price {
+        if lst.price > max { return Err(Error::PriceAboveMax); }
+    }
+    if let Some(n) = nonce {
+        let cur: u32 = persist_get(env, &(LST_NON, id)).unwrap_or(0);
+        if n != cur { return Err(Error::ListingNonceMismatch); }
+    }
 
-        // Khấu trừ người gửi (một lần)
-        let new_from = from_bal - total_deduct;
-        env.storage().persistent().set(&(BAL, &from), &new_from);
+    let data: NftData = persist_get(env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+    if data.owner != lst.seller { return Err(Error::ListingOwnerMismatch); }
+    if buyer == lst.seller { return Err(Error::SelfBuy); }
+    if lst.price <= 0 { return Err(Error::BadPrice); }
 
-        // Cộng tổng phí cho admin (một lần)
-        let admin_bal: i128 = env.storage().persistent().get(&(BAL, &admin)).unwrap_or(0);
-        let new_admin = admin_bal.checked_add(total_fee).expect("BAL_OVERFLOW");
-        env.storage().persistent().set(&(BAL, &admin), &new_admin);
+    let buyer_bal: i128 = persist_get(env, &(BAL, &buyer)).unwrap_or(0);
+    if buyer_bal < lst.price { return Err(Error::InsufficientBalance); }
 
-        log!(&env, "TRANSFER_BATCH: from={} n_recipients={} sum_amounts={} total_fee_raw={}", from, n, sum_amounts, total_fee);
-        total_fee
+    // Chia tiền: phí giao dịch → treasury, royalty → recipient, còn lại → seller
+    let quote = quote_sale(env, id, &lst)?;
+    move_balance(env, &buyer, &lst.seller, quote.seller_net)?;
+    if let Some(recipient) = quote.royalty_recipient {
+        move_balance(env, &buyer, &recipient, quote.royalty)?;
+        RoyaltyPaid { id, recipient, amount: quote.royalty }.publish(env);
+    }
+    if quote.protocol_fee > 0 {
+        move_balance(env, &buyer, &fee_recipient(env), quote.protocol_fee)?;
     }
 
-    /*-------------------------------------------------------------------------*
-     | mint_nft (ADMIN)
-     *-------------------------------------------------------------------------*/
-    pub fn mint_nft(env: Env, to: Address, pixels: Bytes) -> u32 {
-        require_inited(&env);
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-        admin.require_auth();
-
-        if pixels.len() != NFT_PIXELS { panic!("PIXELS_LEN_81"); }
-        for i in 0..pixels.len() {
-            let v = pixels.get_unchecked(i);
-            if v > 31 { panic!("PIXEL_OUT_OF_RANGE"); }
-        }
+    nft_transfer_internal(env, lst.seller.clone(), buyer.clone(), id)?;
+    remove_listing(env, id);
 
-        if env.storage().persistent().has(&(UNIQ, &pixels)) {
-            panic!("DUPLICATE_VALUE");
-        }
+    MarketBuy { id, buyer: buyer.clone(), seller: lst.seller.clone(), price: lst.price }.publish(env);
+    log!(env, "BUY id={} buyer={} price={}", id, buyer, lst.price);
+    Ok(())
+}
 
-        let cur: i128 = env.storage().instance().get(&NFT_SUP).unwrap_or(0);
-        if cur >= NFT_MAX { panic!("NFT_MAX_SUP_REACHED"); }
-        let next: i128 = env.storage().instance().get(&NEXT_ID).unwrap_or(0);
-        if next >= i128::from(u32::MAX) { panic!("NFT_ID_EXHAUSTED"); }
-        let id: u32 = next as u32;
+fn bump_listing_nonce(env: &Env, id: u32) {
+    let cur: u32 = persist_get(env, &(LST_NON, id)).unwrap_or(0);
+    persist_set(env, &(LST_NON, id), &cur.wrapping_add(1));
+}
 
-        let data = NftData { owner: to.clone(), pixels: pixels.clone() };
-        env.storage().persistent().set(&(NFT, id), &data);
+fn listing_expired(env: &Env, lst: &Listing) -> bool {
+    matches!(lst.expires_ledger, Some(exp) if env.ledger().sequence() > exp)
+}
 
-        let mut list: Vec<u32> = env.storage().persistent().get(&(OWN, &to)).unwrap_or(Vec::new(&env));
-        list.push_back(id);
-        env.storage().persistent().set(&(OWN, &to), &list);
+// Listing còn hiệu lực của `id` (None nếu không có hoặc đã hết hạn)
+fn active_listing(env: &Env, id: u32) -> Option<Listing> {
+    let lst: Listing = persist_get(env, &(LST, id))?;
+    if listing_expired(env, &lst) { return None; }
+    Some(lst)
+}
 
-        env.storage().persistent().set(&(UNIQ, &pixels), &id);
+// Xoá LST + LIDS của `id` nếu có
+fn remove_listing(env: &Env, id: u32) {
+    if !env.storage().persistent().has(&(LST, id)) { return; }
+    env.storage().persistent().remove(&(LST, id));
+    env.storage().persistent().remove(&(LST_UPD, id));
+    let mut ids: Vec<u32> = persist_get(env, &LIDS).unwrap_or(Vec::new(env));
+    vec_remove_once(env, &mut ids, id);
+    persist_set(env, &LIDS, &ids);
+}
 
-        if ENABLE_POS_INDEX {
-            for i in 0..NFT_PIXELS {
-                let col: u32 = pixels.get_unchecked(i).into();
-                let key = (IDX, i, col);
-                let mut vec: Vec<u32> = env.storage().persistent().get(&key).unwrap_or(Vec::new(&env));
-                vec.push_back(id);
-                env.storage().persistent().set(&key, &vec);
-            }
-        }
+fn quote_sale(env: &Env, id: u32, lst: &Listing) -> Result<MarketQuote, Error> {
+    let bps: u32 = if is_fee_exempt(env, &lst.seller) { 0 } else { env.storage().instance().get(&SALE_FEE).unwrap_or(0) };
+    let protocol_fee = lst.price.checked_mul(i128::from(bps)).ok_or(Error::FeeMulOverflow)? / BPS_DENOM;
+    let (royalty_recipient, royalty) = match royalty_for(env, id, lst.price)? {
+        Some((recipient, amount)) => (Some(recipient), amount),
+        None => (None, 0),
+    };
+    let seller_net = lst.price - protocol_fee - royalty;
+    Ok(MarketQuote { price: lst.price, protocol_fee, royalty, royalty_recipient, seller_net })
+}
 
-        env.storage().instance().set(&NFT_SUP, &(cur + 1));
-        env.storage().instance().set(&NEXT_ID, &(next + 1));
+fn read_public_mint(env: &Env) -> PublicMintConfig {
+    env.storage().instance().get(&PMINT).unwrap_or(PublicMintConfig {
+        enabled: false,
+        price: 0,
+        wallet_limit: 0,
+        start_ledger: 0,
+        end_ledger: 0,
+    })
+}
 
-        log!(&env, "MINT_NFT id={} to={}", id, to);
-        id
+// Phát hành thêm FT cho `to` (đã kiểm tra quyền / pause / amount > 0)
+fn ft_mint(env: &Env, to: Address, amount: i128) -> Result<(), Error> {
+    let supply: i128 = env.storage().instance().get(&SUPPLY).unwrap_or(0);
+    let new_supply = supply.checked_add(amount).ok_or(Error::SupplyOverflow)?;
+    if let Some(cap) = env.storage().instance().get::<Symbol, i128>(&MAX_SUP) {
+        if new_supply > cap { return Err(Error::MaxSupplyExceeded); }
     }
 
-    /*-------------------------------------------------------------------------*
-     | nft_index_range (ADMIN)
-     *-------------------------------------------------------------------------*/
-    pub fn nft_index_range(env: Env, id: u32, start: u32, end: u32) {
-        require_inited(&env);
-        if end > NFT_PIXELS || start >= end { panic!("BAD_RANGE"); }
+    let bal: i128 = persist_get(env, &(BAL, &to)).unwrap_or(0);
+    persist_set(env, &(BAL, &to), &bal.checked_add(amount).ok_or(Error::BalOverflow)?);
+    env.storage().instance().set(&SUPPLY, &new_supply);
 
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-        admin.require_auth();
+    Mint { to: to.clone(), amount }.publish(env);
+    SupplyChange { delta: amount, total_supply: new_supply }.publish(env);
+    log!(env, "MINT to={} amount={} supply={}", to, amount, new_supply);
+    Ok(())
+}
 
-        let data: NftData = env.storage().persistent().get(&(NFT, id)).expect("NFT_NOT_FOUND");
-        let pixels = data.pixels;
+// Đốt FT của from, giảm SUPPLY
+fn burn_balance(env: &Env, from: &Address, amount: i128) -> Result<(), Error> {
+    let bal: i128 = persist_get(env, &(BAL, from)).unwrap_or(0);
+    if bal < amount { return Err(Error::InsufficientBalance); }
+    persist_set(env, &(BAL, from), &(bal - amount));
 
-        for i in start..end {
-            let col: u32 = pixels.get_unchecked(i).into();
-            let key = (IDX, i, col);
-            let mut vec: Vec<u32> = env.storage().persistent().get(&key).unwrap_or(Vec::new(&env));
-            vec.push_back(id);
-            env.storage().persistent().set(&key, &vec);
-        }
+    let supply: i128 = env.storage().instance().get(&SUPPLY).unwrap_or(0);
+    let new_supply = supply - amount;
+    env.storage().instance().set(&SUPPLY, &new_supply);
 
-        log!(&env, "INDEX_RANGE id={} [{}..{})", id, start, end);
-    }
+    Burn { from: from.clone(), amount }.publish(env);
+    SupplyChange { delta: -amount, total_supply: new_supply }.publish(env);
+    Ok(())
+}
 
-    /*-------------------------------------------------------------------------*
-     | nft_transfer
-     *-------------------------------------------------------------------------*/
-    pub fn nft_transfer(env: Env, from: Address, to: Address, id: u32) {
-        require_inited(&env);
-        from.require_auth();
-        if from == to { panic!("SELF_TRANSFER"); }
-        if env.storage().persistent().has(&(LST, id)) { panic!("LISTED"); }
-        nft_transfer_internal(&env, from, to, id);
+// Allowance hết hạn (ledger hiện tại > expiration_ledger) được coi như 0
+fn read_allowance(env: &Env, from: &Address, spender: &Address) -> AllowanceValue {
+    let key = (ALLOW, from.clone(), spender.clone());
+    match env.storage().temporary().get::<_, AllowanceValue>(&key) {
+        Some(a) if a.expiration_ledger >= env.ledger().sequence() => a,
+        Some(a) => AllowanceValue { amount: 0, expiration_ledger: a.expiration_ledger },
+        None => AllowanceValue { amount: 0, expiration_ledger: 0 },
     }
+}
 
-    pub fn nft_total(env: Env) -> u32 {
-        let cur: i128 = env.storage().instance().get(&NFT_SUP).unwrap_or(0);
-        cur as u32
-    }
+fn write_allowance(env: &Env, from: &Address, spender: &Address, amount: i128, expiration_ledger: u32) -> Result<(), Error> {
+    let seq = env.ledger().sequence();
+    if amount > 0 && expiration_ledger < seq { return Err(Error::BadExpirationLedger); }
+    // extend_ttl vượt max_ttl sẽ trap → báo lỗi trước
+    if amount > 0 && expiration_ledger - seq > env.storage().max_ttl() { return Err(Error::BadExpirationLedger); }
 
-    pub fn nft_get(env: Env, id: u32) -> (Address, Bytes) {
-        let data: NftData = env.storage().persistent().get(&(NFT, id)).expect("NFT_NOT_FOUND");
-        (data.owner, data.pixels)
-    }
+    let key = (ALLOW, from.clone(), spender.clone());
+    env.storage().temporary().set(&key, &AllowanceValue { amount, expiration_ledger });
 
-    pub fn nft_value(env: Env, id: u32) -> Bytes {
-        let data: NftData = env.storage().persistent().get(&(NFT, id)).expect("NFT_NOT_FOUND");
-        data.pixels
+    // Giữ entry sống tới expiration_ledger
+    if amount > 0 {
+        let live_for = expiration_ledger - seq;
+        env.storage().temporary().extend_ttl(&key, live_for, live_for);
     }
+    Ok(())
+}
 
-    pub fn nft_find_by_value(env: Env, pixels: Bytes) -> Option<u32> {
-        env.storage().persistent().get(&(UNIQ, &pixels))
+fn spend_allowance(env: &Env, from: &Address, spender: &Address, amount: i128) -> Result<(), Error> {
+    let a = read_allowance(env, from, spender);
+    if a.amount < amount { return Err(Error::InsufficientAllowance); }
+    if amount > 0 {
+        write_allowance(env, from, spender, a.amount - amount, a.expiration_ledger)?;
     }
+    Ok(())
+}
 
-    pub fn nft_search_pos_color(env: Env, pos: u32, color: u32) -> Vec<u32> {
-        if pos >= NFT_PIXELS { panic!("POS_RANGE_0_80"); }
-        if color >= 32 { panic!("COLOR_RANGE_0_31"); }
-        let key = (IDX, pos, color);
-        env.storage().persistent().get(&key).unwrap_or(Vec::new(&env))
+// Xoá một phần tử “target” đầu tiên trong Vec<u32>
+fn vec_remove_once(env: &Env, v: &mut Vec<u32>, target: u32) -> bool {
+    let mut found = false;
+    let mut out = Vec::new(env);
+    for i in 0..v.len() {
+        let x = v.get_unchecked(i);
+        if !found && x == target { found = true; } else { out.push_back(x); }
     }
+    *v = out;
+    found
+}
 
-    pub fn nft_ids_of(env: Env, owner: Address) -> Vec<u32> {
-        env.storage().persistent().get(&(OWN, &owner)).unwrap_or(Vec::new(&env))
-    }
+// Di chuyển NFT: from → to, cập nhật danh sách OWN và owner trong NFT(id)
+fn nft_transfer_internal(env: &Env, from: Address, to: Address, id: u32) -> Result<(), Error> {
+    if from == to { return Err(Error::SelfTransfer); }
+    let mut data: NftData = persist_get(env, &(NFT, id))
+        .ok_or(Error::NftNotFound)?;
+    if data.owner != from { return Err(Error::NotOwner); }
 
-    /*-------------------------------------------------------------------------*
-     | Palette
-     *-------------------------------------------------------------------------*/
-    pub fn palette_get(env: Env) -> Vec<u32> {
-        env.storage().instance().get(&PAL).expect("NO_PALETTE")
-    }
+    let mut from_list: Vec<u32> = persist_get(env, &(OWN, &from))
+        .unwrap_or(Vec::new(env));
+    if !vec_remove_once(env, &mut from_list, id) { return Err(Error::OwnershipCorrupted); }
+    persist_set(env, &(OWN, &from), &from_list);
 
-    pub fn palette_set(env: Env, new_palette: Vec<u32>) {
-        require_inited(&env);
-        if new_palette.len() != 32 { panic!("PALETTE_32_REQUIRED"); }
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-        admin.require_auth();
-        env.storage().instance().set(&PAL, &new_palette);
-        log!(&env, "PALETTE_UPDATED");
-    }
+    let mut to_list: Vec<u32> = persist_get(env, &(OWN, &to))
+        .unwrap_or(Vec::new(env));
+    to_list.push_back(id);
+    persist_set(env, &(OWN, &to), &to_list);
 
+    data.owner = to.clone();
+    persist_set(env, &(NFT, id), &data);
+    env.storage().temporary().remove(&(NFT_APR, id));
 
-    // Marketplace
-    pub fn listing_fee_set(env: Env, fee: i128) {
-        require_inited(&env);
-        if fee < 0 { panic!("BAD_FEE"); }
-        let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-        admin.require_auth();
-        env.storage().instance().set(&LSTFEE, &fee);
-        log!(&env, "LISTING_FEE_SET {}", fee);
-    }
+    NftTransfer { id, from, to }.publish(env);
+    Ok(())
+}
 
-    pub fn listing_fee_get(env: Env) -> i128 {
-        env.storage().instance().get::<Symbol, i128>(&LSTFEE).unwrap_or(0)
+// Đúng 81 pixel, mỗi giá trị 0..31
+fn validate_pixels(pixels: &Bytes) -> Result<(), Error> {
+    if pixels.len() != NFT_PIXELS { return Err(Error::PixelsLen81); }
+    for i in 0..pixels.len() {
+        let v = pixels.get_unchecked(i);
+        if v > 31 { return Err(Error::PixelOutOfRange); }
     }
+    Ok(())
+}
 
-    pub fn market_list_nft(env: Env, seller: Address, id: u32, price: i128) {
-        require_inited(&env);
-        seller.require_auth();
-        if price <= 0 { panic!("BAD_PRICE"); }
-
-        let data: NftData = env.storage().persistent().get(&(NFT, id)).expect("NFT_NOT_FOUND");
-        if data.owner != seller { panic!("NOT_OWNER"); }
+fn remove_design(env: &Env, sub: &DesignSubmission) {
+    env.storage().persistent().remove(&(DSGN, sub.id));
+    env.storage().persistent().remove(&(DSGN_UNQ, &sub.pixels));
+    let mut ids: Vec<u32> = persist_get(env, &DSGN_IDS).unwrap_or(Vec::new(env));
+    vec_remove_once(env, &mut ids, sub.id);
+    persist_set(env, &DSGN_IDS, &ids);
+}
 
-        if env.storage().persistent().has(&(LST, id)) { panic!("ALREADY_LISTED"); }
+// Kiểm tra pixel + độc nhất rồi ghi NFT mới cho `to`; trả về id.
+// Pattern đang chờ duyệt (DSGN_UNQ) bị chặn; approve_design gỡ giữ chỗ trước khi gọi vào đây.
+fn mint_nft_internal(env: &Env, to: Address, pixels: Bytes) -> Result<u32, Error> {
+    validate_pixels(&pixels)?;
 
-        let fee: i128 = env.storage().instance().get(&LSTFEE).unwrap_or(0);
-        if fee > 0 {
-            let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
-            let mut seller_bal: i128 = env.storage().persistent().get(&(BAL, &seller)).unwrap_or(0);
-            if seller_bal < fee { panic!("INSUFFICIENT_FOR_FEE"); }
-            seller_bal -= fee;
-            env.storage().persistent().set(&(BAL, &seller), &seller_bal);
+    if env.storage().persistent().has(&(UNIQ, &pixels)) {
+        return Err(Error::DuplicateValue);
+    }
+    if env.storage().persistent().has(&(DSGN_UNQ, &pixels)) {
+        return Err(Error::DesignPending);
+    }
 
-            let admin_bal: i128 = env.storage().persistent().get(&(BAL, &admin)).unwrap_or(0);
-            env.storage().persistent().set(&(BAL, &admin), &(admin_bal.checked_add(fee).expect("BAL_OVERFLOW")));
-        }
+    let cur: i128 = env.storage().instance().get(&NFT_SUP).unwrap_or(0);
+    if cur >= NFT_MAX { return Err(Error::NftMaxSupReached); }
+    let next: i128 = env.storage().instance().get(&NEXT_ID).unwrap_or(0);
+    if next >= i128::from(u32::MAX) { return Err(Error::NftIdExhausted); }
+    let id: u32 = next as u32;
 
-        let lst = Listing { seller: seller.clone(), price };
-        env.storage().persistent().set(&(LST, id), &lst);
+    let data = NftData { owner: to.clone(), pixels: pixels.clone() };
+    persist_set(env, &(NFT, id), &data);
 
-        let mut ids: Vec<u32> = env.storage().instance().get(&LIDS).unwrap_or(Vec::new(&env));
-        ids.push_back(id);
-        env.storage().instance().set(&LIDS, &ids);
+    let mut list: Vec<u32> = persist_get(env, &(OWN, &to)).unwrap_or(Vec::new(env));
+    list.push_back(id);
+    persist_set(env, &(OWN, &to), &list);
 
-        log!(&env, "LIST id={} seller={} price={}", id, seller, price);
-    }
+    persist_set(env, &(UNIQ, &pixels), &id);
 
-    pub fn market_cancel(env: Env, seller: Address, id: u32) {
-        require_inited(&env);
-        seller.require_auth();
+    let bps: u32 = env.storage().instance().get(&ROY_BPS).unwrap_or(0);
+    persist_set(env, &(ROY, id), &RoyaltyInfo { creator: to.clone(), recipient: to.clone(), bps });
 
-        let lst: Listing = env.storage().persistent().get(&(LST, id)).expect("NOT_LISTED");
-        if lst.seller != seller { panic!("NOT_SELLER"); }
+    if ENABLE_POS_INDEX {
+        for i in 0..NFT_PIXELS {
+            let col: u32 = pixels.get_unchecked(i).into();
+            let key = (IDX, i, col);
+            let mut vec: Vec<u32> = persist_get(env, &key).unwrap_or(Vec::new(env));
+            vec.push_back(id);
+            persist_set(env, &key, &vec);
+        }
+        mark_indexed(env, id, 0, NFT_PIXELS);
+    }
 
-        env.storage().persistent().remove(&(LST, id));
+    env.storage().instance().set(&NFT_SUP, &(cur + 1));
+    env.storage().instance().set(&NEXT_ID, &(next + 1));
 
-        let mut ids: Vec<u32> = env.storage().instance().get(&LIDS).unwrap_or(Vec::new(&env));
-        vec_remove_once(&env, &mut ids, id);
-        env.storage().instance().set(&LIDS, &ids);
+    NftMint { id, to: to.clone(), pixels }.publish(env);
 
-        log!(&env, "UNLIST id={} seller={}", id, seller);
-    }
+    log!(env, "MINT_NFT id={} to={}", id, to);
+    Ok(id)
+}
 
-    pub fn market_buy(env: Env, buyer: Address, id: u32) {
-        require_inited(&env);
-        buyer.require_auth();
+fn write_palette(env: &Env, new_palette: Vec<u32>) {
+    env.storage().instance().set(&PAL, &new_palette);
+    PaletteSet { palette: new_palette }.publish(env);
+    log!(env, "PALETTE_UPDATED");
+}
 
-        let lst: Listing = env.storage().persistent().get(&(LST, id)).expect("NOT_LISTED");
+fn write_listing_fee(env: &Env, fee: i128) {
+    let old_fee: i128 = env.storage().instance().get(&LSTFEE).unwrap_or(0);
+    env.storage().instance().set(&LSTFEE, &fee);
+    ListingFeeSet { old_fee, new_fee: fee }.publish(env);
+    log!(env, "LISTING_FEE_SET {}", fee);
+}
 
-        let data: NftData = env.storage().persistent().get(&(NFT, id)).expect("NFT_NOT_FOUND");
-        if data.owner != lst.seller { panic!("LISTING_OWNER_MISMATCH"); }
-        if buyer == lst.seller { panic!("SELF_BUY"); }
-        if lst.price <= 0 { panic!("BAD_PRICE"); }
+fn read_council(env: &Env) -> Result<Council, Error> {
+    env.storage().instance().get(&COUNCIL).ok_or(Error::NoCouncil)
+}
 
-        let mut buyer_bal: i128 = env.storage().persistent().get(&(BAL, &buyer)).unwrap_or(0);
-        if buyer_bal < lst.price { panic!("INSUFFICIENT_BALANCE"); }
-        let mut seller_bal: i128 = env.storage().persistent().get(&(BAL, &lst.seller)).unwrap_or(0);
+// Đã có council → thao tác admin phải đi qua council_execute
+fn require_no_council(env: &Env) -> Result<(), Error> {
+    if env.storage().instance().has(&COUNCIL) { return Err(Error::CouncilRequired); }
+    Ok(())
+}
 
-        buyer_bal -= lst.price;
-        seller_bal = seller_bal.checked_add(lst.price).expect("BAL_OVERFLOW");
-        env.storage().persistent().set(&(BAL, &buyer), &buyer_bal);
-        env.storage().persistent().set(&(BAL, &lst.seller), &seller_bal);
+fn write_council(env: &Env, signers: Vec<Address>, threshold: u32) -> Result<(), Error> {
+    if signers.is_empty() && threshold == 0 {
+        env.storage().instance().remove(&COUNCIL);
+    } else {
+        if threshold == 0 || threshold > signers.len() { return Err(Error::BadCouncil); }
+        for i in 0..signers.len() {
+            let s = signers.get_unchecked(i);
+            if signers.last_index_of(&s) != Some(i) { return Err(Error::BadCouncil); }
+        }
+        env.storage().instance().set(&COUNCIL, &Council { signers: signers.clone(), threshold });
+    }
+    CouncilSet { signers, threshold }.publish(env);
+    Ok(())
+}
 
-        nft_transfer_internal(&env, lst.seller.clone(), buyer.clone(), id);
+fn require_council_member(env: &Env, signer: &Address) -> Result<Council, Error> {
+    signer.require_auth();
+    let council = read_council(env)?;
+    if !council.signers.contains(signer) { return Err(Error::NotCouncilMember); }
+    Ok(council)
+}
 
-        env.storage().persistent().remove(&(LST, id));
-        let mut ids: Vec<u32> = env.storage().instance().get(&LIDS).unwrap_or(Vec::new(&env));
-        vec_remove_once(&env, &mut ids, id);
-        env.storage().instance().set(&LIDS, &ids);
+// Số chữ ký hợp lệ theo council hiện tại (bỏ qua signer đã bị loại)
+fn count_approvals(council: &Council, approvals: &Vec<Address>) -> u32 {
+    approvals.iter().filter(|a| council.signers.contains(a)).count() as u32
+}
 
-        log!(&env, "BUY id={} buyer={} price={}", id, buyer, lst.price);
-    }
+fn load_open_proposal(env: &Env, id: u32) -> Result<Proposal, Error> {
+    let prop: Proposal = persist_get(env, &(PROP, id)).ok_or(Error::ProposalNotFound)?;
+    if prop.executed { return Err(Error::ProposalExecuted); }
+    if env.ledger().sequence() > prop.expires_ledger { return Err(Error::ProposalExpired); }
+    Ok(prop)
+}
 
-    pub fn market_get(env: Env, id: u32) -> Option<(Address, i128)> {
-        let maybe: Option<Listing> = env.storage().persistent().get(&(LST, id));
-        match maybe {
-            Some(l) => Some((l.seller, l.price)),
-            None => None,
+fn execute_action(env: &Env, action: AdminAction) -> Result<(), Error> {
+    match action {
+        AdminAction::MintNft(to, pixels) => {
+            require_not_paused(env, Feature::Minting)?;
+            mint_nft_internal(env, to, pixels)?;
         }
+        AdminAction::PaletteSet(palette) => {
+            if palette.len() != 32 { return Err(Error::Palette32Required); }
+            if timelock_delay(env) > 0 {
+                schedule_change(env, env.current_contract_address(), TimelockChange::Palette(palette))?;
+            } else {
+                write_palette(env, palette);
+            }
+        }
+        AdminAction::ListingFeeSet(fee) => {
+            if fee < 0 { return Err(Error::BadFee); }
+            if timelock_delay(env) > 0 {
+                schedule_change(env, env.current_contract_address(), TimelockChange::ListingFee(fee))?;
+            } else {
+                write_listing_fee(env, fee);
+            }
+        }
+        AdminAction::ProposeAdmin(new_admin) => {
+            let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+            env.storage().instance().set(&PEND_ADM, &new_admin);
+            AdminProposed { admin, pending: new_admin }.publish(env);
+        }
+        AdminAction::CouncilSet(signers, threshold) => write_council(env, signers, threshold)?,
+        AdminAction::Upgrade(wasm_hash) => write_wasm(env, wasm_hash),
+        AdminAction::TtlConfigSet(config) => write_ttl_config(env, config)?,
+        AdminAction::GrantRole(role, account) => write_role(env, role, account, true),
+        AdminAction::RevokeRole(role, account) => write_role(env, role, account, false),
+        AdminAction::FtMint(to, amount) => {
+            require_not_paused(env, Feature::Minting)?;
+            if amount <= 0 { return Err(Error::BadAmount); }
+            ft_mint(env, to, amount)?;
+        }
+        AdminAction::NftBurn(id) => {
+            let data: NftData = persist_get(env, &(NFT, id)).ok_or(Error::NftNotFound)?;
+            nft_burn_internal(env, id, data)?;
+        }
+        AdminAction::TimelockDelay(delay) => {
+            if delay > TL_MAX_DELAY { return Err(Error::BadTimelockDelay); }
+            if delay < timelock_delay(env) {
+                schedule_change(env, env.current_contract_address(), TimelockChange::Delay(delay))?;
+            } else {
+                write_timelock_delay(env, delay);
+            }
+        }
+        AdminAction::TimelockCancel(id) => {
+            if !env.storage().persistent().has(&(TL_Q, id)) { return Err(Error::TimelockNotFound); }
+            remove_scheduled(env, id);
+            TimelockCancelled { id }.publish(env);
+        }
+        AdminAction::TreasurySet(treasury) => write_treasury(env, treasury),
     }
+    Ok(())
+}
 
-    pub fn market_list_ids(env: Env) -> Vec<u32> {
-        env.storage().instance().get(&LIDS).unwrap_or(Vec::new(&env))
-    }
-
+fn write_wasm(env: &Env, wasm_hash: BytesN<32>) {
+    env.deployer().update_current_contract_wasm(wasm_hash.clone());
+    Upgraded { wasm_hash }.publish(env);
+}
 
-    // | Token read tiện ích
-    pub fn name(env: Env) -> String {
-        env.storage().instance().get::<Symbol, String>(&NAME).expect("NO_NAME")
+fn write_ttl_config(env: &Env, config: TtlConfig) -> Result<(), Error> {
+    let max = env.storage().max_ttl();
+    if config.instance_threshold > config.instance_extend_to || config.instance_extend_to > max {
+        return Err(Error::BadTtlConfig);
     }
-    pub fn symbol(env: Env) -> String {
-        env.storage().instance().get::<Symbol, String>(&SYMBOL_).expect("NO_SYMBOL")
+    if config.persistent_threshold > config.persistent_extend_to || config.persistent_extend_to > max {
+        return Err(Error::BadTtlConfig);
     }
-    pub fn decimals(env: Env) -> u32 {
-        env.storage().instance().get::<Symbol, u32>(&DECIMALS).expect("NO_DECIMALS")
+    env.storage().instance().set(&TTL_CFG, &config);
+    TtlConfigSet { config }.publish(env);
+    Ok(())
+}
+
+fn write_role(env: &Env, role: Role, account: Address, granted: bool) {
+    if granted {
+        persist_set(env, &(ROLE, role, &account), &true);
+        RoleGranted { role, account }.publish(env);
+    } else {
+        env.storage().persistent().remove(&(ROLE, role, &account));
+        RoleRevoked { role, account }.publish(env);
     }
-    pub fn total_supply(env: Env) -> i128 {
-        env.storage().instance().get::<Symbol, i128>(&SUPPLY).expect("NO_SUPPLY")
+}
+
+fn write_treasury(env: &Env, treasury: Address) {
+    env.storage().instance().set(&TREASURY, &treasury);
+    TreasurySet { treasury }.publish(env);
+}
+
+fn timelock_delay(env: &Env) -> u32 {
+    env.storage().instance().get(&TL_DELAY).unwrap_or(0)
+}
+
+fn write_timelock_delay(env: &Env, delay: u32) {
+    let old_delay = timelock_delay(env);
+    env.storage().instance().set(&TL_DELAY, &delay);
+    TimelockDelaySet { old_delay, new_delay: delay }.publish(env);
+}
+
+// Cùng vai trò với setter trực tiếp; Delay chỉ ADMIN
+fn require_change_auth(env: &Env, change: &TimelockChange, caller: &Address) -> Result<(), Error> {
+    match change {
+        TimelockChange::ListingFee(_) => {
+            require_role(env, Role::FeeManager, caller)?;
+            require_no_council(env)
+        }
+        TimelockChange::Palette(_) => {
+            require_role(env, Role::PaletteManager, caller)?;
+            require_no_council(env)
+        }
+        TimelockChange::Delay(_) => {
+            caller.require_auth();
+            let admin: Address = env.storage().instance().get(&ADMIN).unwrap();
+            if *caller != admin { return Err(Error::MissingRole); }
+            require_no_council(env)
+        }
+        TimelockChange::TransferFee(_) | TimelockChange::SaleFee(_) => {
+            require_role(env, Role::FeeManager, caller)
+        }
     }
-    pub fn balance_of(env: Env, of: Address) -> i128 {
-        env.storage().persistent().get(&(BAL, &of)).unwrap_or(0)
+}
+
+fn validate_change(change: &TimelockChange) -> Result<(), Error> {
+    match change {
+        TimelockChange::ListingFee(fee) if *fee < 0 => Err(Error::BadFee),
+        TimelockChange::Palette(palette) if palette.len() != 32 => Err(Error::Palette32Required),
+        TimelockChange::Delay(delay) if *delay > TL_MAX_DELAY => Err(Error::BadTimelockDelay),
+        TimelockChange::TransferFee(config) => validate_fee_config(config),
+        TimelockChange::SaleFee(bps) if *bps > MAX_SALE_FEE_BPS => Err(Error::BadFee),
+        _ => Ok(()),
     }
 }
 
+fn schedule_change(env: &Env, scheduled_by: Address, change: TimelockChange) -> Result<u32, Error> {
+    let eta_ledger = env.ledger().sequence()
+        .checked_add(timelock_delay(env))
+        .ok_or(Error::BadTimelockDelay)?;
+    let id: u32 = env.storage().instance().get(&TL_NID).unwrap_or(0);
+    let item = ScheduledChange { id, change: change.clone(), scheduled_by, eta_ledger };
+    persist_set(env, &(TL_Q, id), &item);
+
+    let mut ids: Vec<u32> = persist_get(env, &TL_IDS).unwrap_or(Vec::new(env));
+    ids.push_back(id);
+    persist_set(env, &TL_IDS, &ids);
+    env.storage().instance().set(&TL_NID, &(id + 1));
+
+    TimelockScheduled { id, change, eta_ledger }.publish(env);
+    Ok(id)
+}
 
-fn require_inited(env: &Env) {
-    if !env.storage().instance().has(&INITED) { panic!("NOT_INITED"); }
+fn remove_scheduled(env: &Env, id: u32) {
+    env.storage().persistent().remove(&(TL_Q, id));
+    let mut ids: Vec<u32> = persist_get(env, &TL_IDS).unwrap_or(Vec::new(env));
+    vec_remove_once(env, &mut ids, id);
+    persist_set(env, &TL_IDS, &ids);
 }
 
-// Xoá một phần tử “target” đầu tiên trong Vec<u32>
-fn vec_remove_once(env: &Env, v: &mut Vec<u32>, target: u32) -> bool {
-    let mut found = false;
-    let mut out = Vec::new(env);
-    for i in 0..v.len() {
-        let x = v.get_unchecked(i);
-        if !found && x == target { found = true; } else { out.push_back(x); }
+fn nft_burn_internal(env: &Env, id: u32, data: NftData) -> Result<(), Error> {
+    let NftData { owner, pixels } = data;
+
+    let mut owned: Vec<u32> = persist_get(env, &(OWN, &owner)).unwrap_or(Vec::new(env));
+    if !vec_remove_once(env, &mut owned, id) { return Err(Error::OwnershipCorrupted); }
+    persist_set(env, &(OWN, &owner), &owned);
+
+    // Chỉ dọn các vị trí đã index (IDX_MASK), tránh đọc đủ 81 key IDX;
+    // NFT index trước v4 không có mask → quét cả 81 vị trí
+    let legacy: u32 = env.storage().instance().get(&IDX_OLD).unwrap_or(0);
+    let mask: u128 = if id < legacy {
+        (1u128 << NFT_PIXELS) - 1
+    } else {
+        persist_get(env, &(IDX_MASK, id)).unwrap_or(0)
+    };
+    if mask != 0 {
+        for i in 0..NFT_PIXELS {
+            if mask & (1u128 << i) == 0 { continue; }
+            let col: u32 = pixels.get_unchecked(i).into();
+            let key = (IDX, i, col);
+            if let Some(mut vec) = persist_get::<_, Vec<u32>>(env, &key) {
+                while vec_remove_once(env, &mut vec, id) {}
+                persist_set(env, &key, &vec);
+            }
+        }
+        env.storage().persistent().remove(&(IDX_MASK, id));
     }
-    *v = out;
-    found
+
+    remove_listing(env, id);
+    env.storage().temporary().remove(&(NFT_APR, id));
+    env.storage().persistent().remove(&(UNIQ, &pixels));
+    env.storage().persistent().remove(&(ROY, id));
+    env.storage().persistent().remove(&(NFT, id));
+
+    // NEXT_ID giữ nguyên → id không bị dùng lại
+    let cur: i128 = env.storage().instance().get(&NFT_SUP).unwrap_or(0);
+    env.storage().instance().set(&NFT_SUP, &(cur - 1));
+
+    NftBurn { id, owner: owner.clone(), pixels }.publish(env);
+    log!(env, "BURN_NFT id={} owner={}", id, owner);
+    Ok(())
 }
 
-// Di chuyển NFT: from → to, cập nhật danh sách OWN và owner trong NFT(id)
-fn nft_transfer_internal(env: &Env, from: Address, to: Address, id: u32) {
-    if from == to { panic!("SELF_TRANSFER"); }
-    let mut data: NftData = env.storage().persistent()
-        .get(&(NFT, id))
-        .expect("NFT_NOT_FOUND");
-    if data.owner != from { panic!("NOT_OWNER"); }
-
-    let mut from_list: Vec<u32> = env.storage().persistent()
-        .get(&(OWN, &from))
-        .unwrap_or(Vec::new(env));
-    if !vec_remove_once(env, &mut from_list, id) { panic!("OWNERSHIP_CORRUPTED"); }
-    env.storage().persistent().set(&(OWN, &from), &from_list);
+// Ghi nhận vị trí [start, end) của NFT `id` đã có trong IDX
+fn mark_indexed(env: &Env, id: u32, start: u32, end: u32) {
+    let mut mask: u128 = persist_get(env, &(IDX_MASK, id)).unwrap_or(0);
+    for i in start..end { mask |= 1u128 << i; }
+    persist_set(env, &(IDX_MASK, id), &mask);
+}
 
-    let mut to_list: Vec<u32> = env.storage().persistent()
-        .get(&(OWN, &to))
-        .unwrap_or(Vec::new(env));
-    to_list.push_back(id);
-    env.storage().persistent().set(&(OWN, &to), &to_list);
+fn read_nft_approval(env: &Env, id: u32) -> Option<Address> {
+    let a: NftApproval = env.storage().temporary().get(&(NFT_APR, id))?;
+    if env.ledger().sequence() > a.expiration_ledger { return None; }
+    Some(a.spender)
+}
 
-    data.owner = to.clone();
-    env.storage().persistent().set(&(NFT, id), &data);
+fn is_nft_operator(env: &Env, owner: &Address, operator: &Address) -> bool {
+    persist_get(env, &(NFT_OPR, owner, operator)).unwrap_or(false)
 }
 
 // Tính base * 10^decimals, kiểm tra overflow
@@ -504,3 +3137,5 @@ fn default_palette(env: &Env) -> Vec<u32> {
         ],
     )
 }
+
+mod test;
@@ -1,21 +1,1268 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{vec, Env, String};
+use soroban_sdk::testutils::{storage::Persistent as _, Address as _, Events, Ledger};
+use soroban_sdk::{vec, Bytes, Env, Event, String};
+
+fn setup(env: &Env) -> (SimpleTokenNftClient<'_>, Address) {
+    env.mock_all_auths();
+    let contract_id = env.register(SimpleTokenNft, ());
+    let client = SimpleTokenNftClient::new(env, &contract_id);
+    let admin = Address::generate(env);
+    client.init(
+        &admin,
+        &String::from_str(env, "NFTMarket"),
+        &String::from_str(env, "NFTM"),
+        &3,
+        &100_000_000_000,
+        &None,
+        &None,
+    );
+    (client, admin)
+}
+
+fn pixels(env: &Env, seed: u8) -> Bytes {
+    let mut out = Bytes::new(env);
+    for i in 0..NFT_PIXELS {
+        out.push_back(((i as u8).wrapping_add(seed)) % 32);
+    }
+    out
+}
+
+#[test]
+fn test_transfer_from_spends_allowance_with_fee() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let carol = Address::generate(&env);
+
+    client.transfer(&admin, &alice, &1_000);
+    let admin_bal = client.balance(&admin);
+
+    client.approve(&alice, &bob, &500, &100);
+    assert_eq!(client.allowance(&alice, &bob), 500);
+
+    client.transfer_from(&bob, &alice, &carol, &100);
+    assert_eq!(client.balance(&carol), 100);
+    assert_eq!(client.balance(&alice), 1_000 - 100 - TRANSFER_FEE_RAW);
+    assert_eq!(client.balance(&admin), admin_bal + TRANSFER_FEE_RAW);
+    assert_eq!(client.allowance(&alice, &bob), 500 - 100);
+
+    // Phí trừ vào số dư `from`, nên tiêu hết đúng phần allowance còn lại vẫn được
+    client.transfer_from(&bob, &alice, &carol, &400);
+    assert_eq!(client.allowance(&alice, &bob), 0);
+    assert_eq!(client.balance(&alice), 1_000 - 500 - 2 * TRANSFER_FEE_RAW);
+
+    assert_eq!(
+        client.try_transfer_from(&bob, &alice, &carol, &1),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+}
 
 #[test]
-fn test() {
+fn test_allowance_expires() {
     let env = Env::default();
-    let contract_id = env.register(Contract, ());
-    let client = ContractClient::new(&env, &contract_id);
+    let (client, admin) = setup(&env);
+    let bob = Address::generate(&env);
+
+    client.approve(&admin, &bob, &500, &10);
+    assert_eq!(client.allowance(&admin, &bob), 500);
 
-    let words = client.hello(&String::from_str(&env, "Dev"));
+    env.ledger().set_sequence_number(11);
+    assert_eq!(client.allowance(&admin, &bob), 0);
     assert_eq!(
-        words,
+        client.try_burn_from(&bob, &admin, &1),
+        Err(Ok(Error::InsufficientAllowance))
+    );
+}
+
+#[test]
+fn test_approve_rejects_expiration_beyond_max_ttl() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let bob = Address::generate(&env);
+    let max_ttl = env.as_contract(&client.address, || env.storage().max_ttl());
+    let seq = env.ledger().sequence();
+
+    assert_eq!(
+        client.try_approve(&admin, &bob, &500, &(seq + max_ttl + 1)),
+        Err(Ok(Error::BadExpirationLedger))
+    );
+    client.approve(&admin, &bob, &500, &(seq + max_ttl));
+    assert_eq!(client.allowance(&admin, &bob), 500);
+}
+
+#[test]
+fn test_burn_reduces_supply() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let bob = Address::generate(&env);
+
+    let supply = client.total_supply();
+    client.burn(&admin, &1_000);
+    assert_eq!(client.total_supply(), supply - 1_000);
+
+    client.approve(&admin, &bob, &300, &100);
+    client.burn_from(&bob, &admin, &300);
+    assert_eq!(client.total_supply(), supply - 1_300);
+    assert_eq!(client.balance(&admin), supply - 1_300);
+    assert_eq!(client.allowance(&admin, &bob), 0);
+}
+
+#[test]
+fn test_errors_have_stable_codes() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let bob = Address::generate(&env);
+
+    assert_eq!(
+        client.try_init(
+            &admin,
+            &String::from_str(&env, "NFTMarket"),
+            &String::from_str(&env, "NFTM"),
+            &3,
+            &100_000_000_000,
+            &None,
+            &None,
+        ),
+        Err(Ok(Error::AlreadyInit))
+    );
+    assert_eq!(client.try_transfer(&admin, &admin, &1), Err(Ok(Error::SelfTransfer)));
+    assert_eq!(
+        client.try_transfer(&bob, &admin, &1),
+        Err(Ok(Error::InsufficientBalanceWithFee))
+    );
+    assert_eq!(client.try_nft_get(&0), Err(Ok(Error::NftNotFound)));
+    assert_eq!(client.try_market_buy(&bob, &0), Err(Ok(Error::NotListed)));
+    assert_eq!(Error::InsufficientBalanceWithFee as u32, 17);
+    assert_eq!(Error::PixelsLen81 as u32, 30);
+}
+
+#[test]
+fn test_market_buy_publishes_events() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+
+    client.transfer(&admin, &seller, &100);
+    client.transfer(&admin, &buyer, &1_000);
+    let id = client.mint_nft(&admin, &seller, &pixels(&env, 0));
+    client.market_list_nft(&seller, &id, &500, &None);
+
+    client.market_buy(&buyer, &id);
+    let transfer = Transfer { from: buyer.clone(), to: seller.clone(), amount: 500 };
+    let nft_transfer = NftTransfer { id, from: seller.clone(), to: buyer.clone() };
+    let buy = MarketBuy { id, buyer: buyer.clone(), seller: seller.clone(), price: 500 };
+    assert_eq!(
+        env.events().all(),
         vec![
             &env,
-            String::from_str(&env, "Hello"),
-            String::from_str(&env, "Dev"),
+            (client.address.clone(), transfer.topics(&env), transfer.data(&env)),
+            (client.address.clone(), nft_transfer.topics(&env), nft_transfer.data(&env)),
+            (client.address.clone(), buy.topics(&env), buy.data(&env)),
         ]
     );
 }
+
+#[test]
+fn test_transfer_fee_bps_with_caps_goes_to_treasury() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let treasury = Address::generate(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    client.treasury_set(&treasury);
+    assert_eq!(client.treasury_get(), treasury);
+
+    // 1% với min 5, max 50
+    client.transfer_fee_set(&admin, &FeeMode::Bps, &100, &Some(5), &Some(50));
+    assert_eq!(client.transfer_fee_quote(&100), 5);
+    assert_eq!(client.transfer_fee_quote(&2_000), 20);
+    assert_eq!(client.transfer_fee_quote(&1_000_000), 50);
+
+    client.transfer(&admin, &alice, &10_000);
+    assert_eq!(client.balance(&treasury), 50);
+
+    let tos = vec![&env, bob.clone(), treasury.clone()];
+    let amounts = vec![&env, 100i128, 2_000i128];
+    assert_eq!(client.transfer_batch(&alice, &tos, &amounts), 25);
+    assert_eq!(client.balance(&alice), 10_000 - 2_100 - 25);
+    assert_eq!(client.balance(&treasury), 50 + 2_000 + 25);
+
+    assert_eq!(
+        client.try_transfer_fee_set(&admin, &FeeMode::Bps, &10_001, &None, &None),
+        Err(Ok(Error::BadFeeConfig))
+    );
+    assert_eq!(
+        client.try_transfer_fee_set(&admin, &FeeMode::Flat, &1, &Some(10), &Some(5)),
+        Err(Ok(Error::BadFeeConfig))
+    );
+}
+
+#[test]
+fn test_fee_exempt_skips_transfer_and_listing_fee() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let partner = Address::generate(&env);
+    let bob = Address::generate(&env);
+
+    client.transfer(&admin, &partner, &1_000);
+    client.fee_exempt_set(&admin, &partner, &true);
+    assert!(client.fee_exempt_get(&partner));
+
+    client.transfer(&partner, &bob, &400);
+    assert_eq!(client.balance(&partner), 600);
+
+    let id = client.mint_nft(&admin, &partner, &pixels(&env, 1));
+    client.market_list_nft(&partner, &id, &50, &None);
+    assert_eq!(client.balance(&partner), 600);
+
+    client.fee_exempt_set(&admin, &partner, &false);
+    assert!(!client.fee_exempt_get(&partner));
+    client.transfer(&partner, &bob, &100);
+    assert_eq!(client.balance(&partner), 500 - TRANSFER_FEE_RAW);
+}
+
+#[test]
+fn test_init_with_decimals_and_allocations() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SimpleTokenNftClient::new(&env, &env.register(SimpleTokenNft, ()));
+    let admin = Address::generate(&env);
+    let team = Address::generate(&env);
+    let name = String::from_str(&env, "NFTMarket");
+    let symbol = String::from_str(&env, "NFTM");
+
+    assert_eq!(
+        client.try_init(&admin, &name, &symbol, &19, &1_000, &None, &None),
+        Err(Ok(Error::BadDecimals))
+    );
+    assert_eq!(
+        client.try_init(&admin, &name, &symbol, &18, &i128::MAX, &None, &None),
+        Err(Ok(Error::SupplyOverflow))
+    );
+    let too_much = vec![&env, GenesisAllocation { to: team.clone(), amount: 1_001 }];
+    assert_eq!(
+        client.try_init(&admin, &name, &symbol, &0, &1_000, &Some(too_much), &None),
+        Err(Ok(Error::AllocationExceedsSupply))
+    );
+
+    let allocs = vec![&env, GenesisAllocation { to: team.clone(), amount: 250 }];
+    client.init(&admin, &name, &symbol, &0, &1_000, &Some(allocs), &None);
+    assert_eq!(client.decimals(), 0);
+    assert_eq!(client.total_supply(), 1_000);
+    assert_eq!(client.balance(&team), 250);
+    assert_eq!(client.balance(&admin), 750);
+}
+
+#[test]
+fn test_admin_mint_respects_max_supply() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SimpleTokenNftClient::new(&env, &env.register(SimpleTokenNft, ()));
+    let admin = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let name = String::from_str(&env, "NFTMarket");
+    let symbol = String::from_str(&env, "NFTM");
+
+    assert_eq!(
+        client.try_init(&admin, &name, &symbol, &3, &1_000, &None, &Some(999)),
+        Err(Ok(Error::MaxSupplyExceeded))
+    );
+    client.init(&admin, &name, &symbol, &3, &1_000, &None, &Some(1_500));
+    assert_eq!(client.max_supply(), Some(1_500_000));
+
+    client.mint(&bob, &400_000);
+    let change = SupplyChange { delta: 400_000, total_supply: 1_400_000 };
+    let last = env.events().all().last().unwrap();
+    assert_eq!(
+        vec![&env, last],
+        vec![&env, (client.address.clone(), change.topics(&env), change.data(&env))]
+    );
+    assert_eq!(client.balance(&bob), 400_000);
+    assert_eq!(client.total_supply(), 1_400_000);
+
+    assert_eq!(client.try_mint(&bob, &100_001), Err(Ok(Error::MaxSupplyExceeded)));
+    client.burn(&bob, &100_000);
+    client.mint(&bob, &200_000);
+    assert_eq!(client.total_supply(), 1_500_000);
+}
+
+#[test]
+fn test_two_step_admin_handover_moves_fees() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let new_admin = Address::generate(&env);
+    let alice = Address::generate(&env);
+
+    assert_eq!(client.try_accept_admin(), Err(Ok(Error::NoPendingAdmin)));
+    client.propose_admin(&new_admin);
+    assert_eq!(client.admin(), admin);
+    assert_eq!(client.pending_admin(), Some(new_admin.clone()));
+
+    client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_admin);
+    assert_eq!(client.admin(), new_admin);
+    assert_eq!(client.pending_admin(), None);
+
+    // Phí transfer chuyển sang admin mới (chưa đặt treasury)
+    client.transfer(&admin, &alice, &100);
+    assert_eq!(client.balance(&new_admin), TRANSFER_FEE_RAW);
+}
+
+#[test]
+fn test_roles_gate_admin_entrypoints() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let minter = Address::generate(&env);
+    let alice = Address::generate(&env);
+
+    assert!(!client.has_role(&Role::Minter, &minter));
+    assert_eq!(
+        client.try_mint_nft(&minter, &alice, &pixels(&env, 2)),
+        Err(Ok(Error::MissingRole))
+    );
+
+    client.grant_role(&Role::Minter, &minter);
+    assert!(client.has_role(&Role::Minter, &minter));
+    let id = client.mint_nft(&minter, &alice, &pixels(&env, 2));
+    assert_eq!(client.nft_ids_of(&alice), vec![&env, id]);
+
+    // Minter không đụng được phí
+    assert_eq!(client.try_listing_fee_set(&minter, &5), Err(Ok(Error::MissingRole)));
+    client.listing_fee_set(&admin, &5);
+
+    client.revoke_role(&Role::Minter, &minter);
+    assert_eq!(
+        client.try_mint_nft(&minter, &alice, &pixels(&env, 3)),
+        Err(Ok(Error::MissingRole))
+    );
+}
+
+#[test]
+fn test_pause_blocks_mutations_but_not_views() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let pauser = Address::generate(&env);
+    let alice = Address::generate(&env);
+
+    client.grant_role(&Role::Pauser, &pauser);
+    let id = client.mint_nft(&admin, &admin, &pixels(&env, 4));
+
+    client.pause_set(&pauser, &Feature::TokenTransfer, &true);
+    assert!(client.is_paused(&Feature::TokenTransfer));
+    assert!(!client.is_paused(&Feature::NftTransfer));
+    assert_eq!(client.try_transfer(&admin, &alice, &10), Err(Ok(Error::Paused)));
+    assert_eq!(client.try_approve(&admin, &alice, &10, &100), Err(Ok(Error::Paused)));
+    assert!(client.balance(&admin) > 0);
+    client.nft_transfer(&admin, &alice, &id);
+
+    client.pause_set(&pauser, &Feature::All, &true);
+    assert_eq!(client.try_nft_transfer(&alice, &admin, &id), Err(Ok(Error::Paused)));
+    assert_eq!(client.try_market_list_nft(&alice, &id, &10, &None), Err(Ok(Error::Paused)));
+    assert_eq!(client.try_market_prune_expired(&10), Err(Ok(Error::Paused)));
+    assert_eq!(client.try_nft_approve(&alice, &admin, &id, &100), Err(Ok(Error::Paused)));
+    assert_eq!(client.try_nft_set_approval_for_all(&alice, &admin, &true), Err(Ok(Error::Paused)));
+    assert_eq!(client.nft_ids_of(&alice), vec![&env, id]);
+
+    client.pause_set(&pauser, &Feature::All, &false);
+    client.pause_set(&pauser, &Feature::TokenTransfer, &false);
+    client.transfer(&admin, &alice, &10);
+}
+
+// Đưa storage về layout v1 (như contract deploy trước khi có VER)
+fn downgrade_to_v1_layout(env: &Env, contract: &Address) {
+    env.as_contract(contract, || {
+        let ids: Vec<u32> = env.storage().persistent().get(&LIDS).unwrap();
+        for id in ids.iter() {
+            let lst: Listing = env.storage().persistent().get(&(LST, id)).unwrap();
+            let old = ListingV2 { seller: lst.seller, price: lst.price };
+            env.storage().persistent().set(&(LST, id), &old);
+        }
+        env.storage().persistent().remove(&LIDS);
+        env.storage().instance().set(&LIDS, &ids);
+        env.storage().instance().remove(&VERSION);
+    });
+}
+
+#[test]
+fn test_migrate_v1_layout_keeps_state() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+
+    client.transfer(&admin, &seller, &1_000);
+    client.transfer(&admin, &buyer, &1_000);
+    let id0 = client.mint_nft(&admin, &seller, &pixels(&env, 5));
+    let id1 = client.mint_nft(&admin, &seller, &pixels(&env, 6));
+    client.market_list_nft(&seller, &id0, &300, &None);
+    client.market_list_nft(&seller, &id1, &400, &None);
+    let seller_bal = client.balance(&seller);
+    let supply = client.total_supply();
+
+    downgrade_to_v1_layout(&env, &client.address);
+    assert_eq!(client.schema_version(), 1);
+    assert_eq!(client.try_market_buy(&buyer, &id0), Err(Ok(Error::MigrationRequired)));
+
+    assert_eq!(client.migrate(), SCHEMA_VERSION);
+    assert_eq!(client.schema_version(), SCHEMA_VERSION);
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id0, id1]);
+    assert_eq!(client.market_get(&id1), Some((seller.clone(), 400)));
+    assert_eq!(client.nft_ids_of(&seller), vec![&env, id0, id1]);
+    assert_eq!(client.balance(&seller), seller_bal);
+    assert_eq!(client.total_supply(), supply);
+    env.as_contract(&client.address, || {
+        assert!(!env.storage().instance().has(&LIDS));
+    });
+
+    client.market_buy(&buyer, &id0);
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id1]);
+    assert_eq!(client.migrate(), SCHEMA_VERSION);
+}
+
+// Bản deploy đầu tiên (commit baseline 23f0d0e, chưa có upgrade / VER), build bằng
+// `cargo build --target wasm32v1-none --release` tại commit đó
+mod baseline {
+    soroban_sdk::contractimport!(file = "testdata/nftmarket_baseline.wasm");
+}
+
+// Wasm hiện tại, do `make build` (stellar contract build) tạo ra; `make test` build trước khi test
+const NFTMARKET_WASM: &[u8] = include_bytes!("../../../target/wasm32v1-none/release/nftmarket.wasm");
+
+#[test]
+fn test_upgrade_then_migrate_keeps_state() {
+    let env = Env::default();
+    env.mock_all_auths();
+    // Parse + chạy wasm tốn budget hơn nhiều so với contract native
+    env.cost_estimate().budget().reset_unlimited();
+
+    // Dựng state bằng API của baseline
+    let address = env.register(baseline::WASM, ());
+    let old = baseline::Client::new(&env, &address);
+    let admin = Address::generate(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    old.init(&admin, &String::from_str(&env, "Pixel"), &String::from_str(&env, "PXL"), &3);
+    old.transfer(&admin, &seller, &1_000);
+    old.transfer(&admin, &buyer, &1_000);
+    let id0 = old.mint_nft(&seller, &pixels(&env, 7));
+    let id1 = old.mint_nft(&seller, &pixels(&env, 8));
+    old.market_list_nft(&seller, &id0, &300);
+    old.market_list_nft(&seller, &id1, &400);
+    let seller_bal = old.balance_of(&seller);
+    let supply = old.total_supply();
+
+    // Baseline chưa có upgrade → thay wasm ngay trong ngữ cảnh contract
+    let wasm_hash = env.deployer().upload_contract_wasm(NFTMARKET_WASM);
+    env.as_contract(&address, || env.deployer().update_current_contract_wasm(wasm_hash));
+    let client = SimpleTokenNftClient::new(&env, &address);
+
+    // Code mới đã chạy nhưng storage còn layout cũ → chặn mọi lệnh ghi
+    assert_eq!(client.schema_version(), 1);
+    assert_eq!(client.try_transfer(&seller, &buyer, &10), Err(Ok(Error::MigrationRequired)));
+    assert_eq!(client.try_market_buy(&buyer, &id0), Err(Ok(Error::MigrationRequired)));
+    assert_eq!(client.try_mint_nft(&admin, &seller, &pixels(&env, 9)), Err(Ok(Error::MigrationRequired)));
+
+    assert_eq!(client.migrate(), SCHEMA_VERSION);
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id0, id1]);
+    assert_eq!(client.market_get(&id1), Some((seller.clone(), 400)));
+    assert_eq!(client.nft_get(&id0), (seller.clone(), pixels(&env, 7)));
+    assert_eq!(client.nft_ids_of(&seller), vec![&env, id0, id1]);
+    assert_eq!(client.balance(&seller), seller_bal);
+    assert_eq!(client.total_supply(), supply);
+
+    client.market_buy(&buyer, &id0);
+    assert_eq!(client.nft_get(&id0).0, buyer);
+
+    // NFT index bởi baseline (không có IDX_MASK) vẫn được dọn khỏi IDX khi burn
+    client.nft_burn(&buyer, &id0);
+    let col: u32 = pixels(&env, 7).get_unchecked(40).into();
+    assert_eq!(client.nft_search_pos_color(&40, &col), Vec::new(&env));
+}
+
+#[test]
+fn test_ttl_extended_on_write_and_bump() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let id = client.mint_nft(&admin, &alice, &pixels(&env, 9));
+
+    let ttl_of = |key: (Symbol, u32)| {
+        env.as_contract(&client.address, || env.storage().persistent().get_ttl(&key))
+    };
+    assert_eq!(ttl_of((NFT, id)), PERSISTENT_TTL_EXTEND_TO);
+
+    let bad = TtlConfig {
+        instance_threshold: 10,
+        instance_extend_to: 5,
+        persistent_threshold: 10,
+        persistent_extend_to: 20,
+    };
+    assert_eq!(client.try_ttl_config_set(&bad), Err(Ok(Error::BadTtlConfig)));
+
+    let cfg = TtlConfig {
+        instance_threshold: DAY_IN_LEDGERS,
+        instance_extend_to: 2 * DAY_IN_LEDGERS,
+        persistent_threshold: DAY_IN_LEDGERS,
+        persistent_extend_to: 200 * DAY_IN_LEDGERS,
+    };
+    client.ttl_config_set(&cfg);
+    assert_eq!(client.ttl_config_get(), cfg);
+
+    // Ai cũng có thể trả phí gia hạn
+    env.ledger().with_mut(|l| l.sequence_number += 1_000);
+    client.bump_nft(&id);
+    assert_eq!(ttl_of((NFT, id)), 200 * DAY_IN_LEDGERS);
+    client.bump_account(&alice);
+    env.as_contract(&client.address, || {
+        assert_eq!(env.storage().persistent().get_ttl(&(OWN, &alice)), 200 * DAY_IN_LEDGERS);
+    });
+    assert_eq!(client.try_bump_nft(&(id + 1)), Err(Ok(Error::NftNotFound)));
+}
+
+#[test]
+fn test_council_two_of_three_executes_action() {
+    let env = Env::default();
+    let (client, _admin) = setup(&env);
+    let s1 = Address::generate(&env);
+    let s2 = Address::generate(&env);
+    let s3 = Address::generate(&env);
+    let outsider = Address::generate(&env);
+    let signers = vec![&env, s1.clone(), s2.clone(), s3.clone()];
+
+    assert_eq!(client.try_council_set(&signers, &4), Err(Ok(Error::BadCouncil)));
+    assert_eq!(
+        client.try_council_set(&vec![&env, s1.clone(), s1.clone()], &1),
+        Err(Ok(Error::BadCouncil))
+    );
+    client.council_set(&signers, &2);
+    assert_eq!(client.council_get(), Some(Council { signers, threshold: 2 }));
+
+    let action = AdminAction::ListingFeeSet(77);
+    assert_eq!(
+        client.try_council_propose(&outsider, &action, &100),
+        Err(Ok(Error::NotCouncilMember))
+    );
+    let id = client.council_propose(&s1, &action, &100);
+    assert_eq!(client.try_council_execute(&id), Err(Ok(Error::NotEnoughApprovals)));
+    assert_eq!(client.try_council_approve(&s1, &id), Err(Ok(Error::AlreadyApproved)));
+
+    assert_eq!(client.council_approve(&s2, &id), 2);
+    client.council_execute(&id);
+    let ev = ProposalExecuted { id, action };
+    assert_eq!(
+        env.events().all().slice(1..),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+    assert_eq!(client.listing_fee_get(), 77);
+    assert!(client.council_proposal(&id).unwrap().executed);
+    assert_eq!(client.try_council_execute(&id), Err(Ok(Error::ProposalExecuted)));
+
+    // Hết hạn trước khi đủ chữ ký
+    let mint = AdminAction::MintNft(outsider.clone(), pixels(&env, 11));
+    let id2 = client.council_propose(&s3, &mint, &150);
+    env.ledger().with_mut(|l| l.sequence_number = 151);
+    assert_eq!(client.try_council_approve(&s1, &id2), Err(Ok(Error::ProposalExpired)));
+    assert_eq!(client.nft_total(), 0);
+}
+
+#[test]
+fn test_council_blocks_direct_admin_actions() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let minter = Address::generate(&env);
+    let s1 = Address::generate(&env);
+    let s2 = Address::generate(&env);
+    client.grant_role(&Role::Minter, &minter);
+    client.grant_role(&Role::PaletteManager, &minter);
+    client.council_set(&vec![&env, s1.clone(), s2.clone()], &2);
+
+    // Có council → ADMIN (hay người có vai trò) không tự làm được
+    let px = pixels(&env, 12);
+    assert_eq!(client.try_mint_nft(&admin, &admin, &px), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.try_mint_nft(&minter, &minter, &px), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_listing_fee_set(&admin, &0), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.try_palette_set(&minter, &client.palette_get()), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_propose_admin(&minter), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_council_set(&vec![&env, admin.clone()], &1), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(
+        client.try_timelock_schedule(&minter, &TimelockChange::Palette(client.palette_get())),
+        Err(Ok(Error::CouncilRequired))
+    );
+    let wasm_hash = BytesN::from_array(&env, &[1; 32]);
+    assert_eq!(client.try_upgrade(&wasm_hash), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_mint(&admin, &1), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_treasury_set(&admin), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_grant_role(&Role::FeeManager, &admin), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_revoke_role(&Role::Minter, &minter), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_ttl_config_set(&client.ttl_config_get()), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_timelock_delay_set(&10), Err(Ok(Error::CouncilRequired)));
+    assert_eq!(client.try_nft_admin_burn(&0), Err(Ok(Error::CouncilRequired)));
+
+    // ADMIN không còn mặc nhiên có vai trò → không vòng qua mint_public / approve_design / phí
+    assert_eq!(client.try_sale_fee_set(&admin, &100), Err(Ok(Error::MissingRole)));
+    assert_eq!(client.try_transfer_fee_set(&admin, &FeeMode::Flat, &5, &None, &None), Err(Ok(Error::MissingRole)));
+    let cfg = PublicMintConfig { enabled: true, price: 0, wallet_limit: 0, start_ledger: 0, end_ledger: 0 };
+    assert_eq!(client.try_public_mint_set(&admin, &cfg), Err(Ok(Error::MissingRole)));
+    let sub = client.submit_design(&admin, &px);
+    assert_eq!(client.try_approve_design(&admin, &sub), Err(Ok(Error::MissingRole)));
+    client.reject_design(&minter, &sub, &None);
+    assert_eq!(client.nft_total(), 0);
+
+    // Các thao tác đó vẫn làm được qua council
+    let run = |action: AdminAction| {
+        let id = client.council_propose(&s1, &action, &100);
+        client.council_approve(&s2, &id);
+        client.council_execute(&id);
+    };
+    let supply = client.total_supply();
+    run(AdminAction::FtMint(minter.clone(), 500));
+    assert_eq!(client.total_supply(), supply + 500);
+    run(AdminAction::GrantRole(Role::FeeManager, minter.clone()));
+    client.sale_fee_set(&minter, &100);
+    run(AdminAction::TreasurySet(minter.clone()));
+    assert_eq!(client.treasury_get(), minter);
+    run(AdminAction::TimelockDelay(10));
+    assert_eq!(client.timelock_delay(), 10);
+
+    // Thay đổi do council xếp hàng: role không huỷ được, council huỷ được
+    run(AdminAction::PaletteSet(client.palette_get()));
+    let queued = client.timelock_pending().get_unchecked(0).id;
+    assert_eq!(client.try_timelock_cancel(&minter, &queued), Err(Ok(Error::CouncilRequired)));
+    run(AdminAction::TimelockCancel(queued));
+    assert_eq!(client.timelock_pending(), Vec::new(&env));
+    run(AdminAction::TimelockDelay(0));
+    assert_eq!(client.timelock_delay(), 10);
+    let delay_change = client.timelock_pending().get_unchecked(0).id;
+    env.ledger().with_mut(|l| l.sequence_number += 10);
+    client.timelock_execute(&delay_change);
+    assert_eq!(client.timelock_delay(), 0);
+
+    // Đổi / tắt council cũng phải qua đề xuất đủ chữ ký
+    let id = client.council_propose(&s1, &AdminAction::CouncilSet(Vec::new(&env), 0), &100);
+    assert_eq!(client.try_council_execute(&id), Err(Ok(Error::NotEnoughApprovals)));
+    client.council_approve(&s2, &id);
+    client.council_execute(&id);
+    assert_eq!(client.council_get(), None);
+    client.mint_nft(&admin, &admin, &px);
+}
+
+#[test]
+fn test_timelock_delays_listing_fee_change() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let fee_mgr = Address::generate(&env);
+    client.grant_role(&Role::FeeManager, &fee_mgr);
+
+    client.timelock_delay_set(&100);
+    assert_eq!(client.try_listing_fee_set(&fee_mgr, &5), Err(Ok(Error::TimelockRequired)));
+    assert_eq!(client.try_timelock_delay_set(&10), Err(Ok(Error::TimelockRequired)));
+
+    let id = client.timelock_schedule(&fee_mgr, &TimelockChange::ListingFee(5));
+    let cancel_id = client.timelock_schedule(&admin, &TimelockChange::ListingFee(9));
+    let pending = client.timelock_pending();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending.get_unchecked(0).eta_ledger, env.ledger().sequence() + 100);
+
+    assert_eq!(client.try_timelock_execute(&id), Err(Ok(Error::TimelockNotReady)));
+    client.timelock_cancel(&fee_mgr, &cancel_id);
+    assert_eq!(client.try_timelock_execute(&cancel_id), Err(Ok(Error::TimelockNotFound)));
+
+    env.ledger().with_mut(|l| l.sequence_number += 100);
+    client.timelock_execute(&id);
+    assert_eq!(client.listing_fee_get(), 5);
+    assert_eq!(client.timelock_pending().len(), 0);
+
+    // Tắt timelock cũng phải chờ
+    let off = client.timelock_schedule(&admin, &TimelockChange::Delay(0));
+    env.ledger().with_mut(|l| l.sequence_number += 100);
+    client.timelock_execute(&off);
+    client.listing_fee_set(&fee_mgr, &7);
+    assert_eq!(client.listing_fee_get(), 7);
+}
+
+#[test]
+fn test_timelock_delays_transfer_fee_change() {
+    let env = Env::default();
+    let (client, _admin) = setup(&env);
+    let fee_mgr = Address::generate(&env);
+    client.grant_role(&Role::FeeManager, &fee_mgr);
+    let old = client.transfer_fee_get();
+
+    client.timelock_delay_set(&100);
+    assert_eq!(
+        client.try_transfer_fee_set(&fee_mgr, &FeeMode::Bps, &100, &None, &None),
+        Err(Ok(Error::TimelockRequired))
+    );
+    let config = TransferFeeConfig { mode: FeeMode::Bps, value: 100, min_fee: Some(1), max_fee: None };
+    let bad = TransferFeeConfig { mode: FeeMode::Bps, value: 10_001, min_fee: None, max_fee: None };
+    assert_eq!(
+        client.try_timelock_schedule(&fee_mgr, &TimelockChange::TransferFee(bad)),
+        Err(Ok(Error::BadFeeConfig))
+    );
+    let id = client.timelock_schedule(&fee_mgr, &TimelockChange::TransferFee(config.clone()));
+    assert_eq!(client.try_timelock_execute(&id), Err(Ok(Error::TimelockNotReady)));
+    assert_eq!(client.transfer_fee_get(), old);
+
+    env.ledger().with_mut(|l| l.sequence_number += 100);
+    client.timelock_execute(&id);
+    assert_eq!(client.transfer_fee_get(), config);
+}
+
+#[test]
+fn test_timelock_delays_sale_fee_change() {
+    let env = Env::default();
+    let (client, _admin) = setup(&env);
+    let fee_mgr = Address::generate(&env);
+    client.grant_role(&Role::FeeManager, &fee_mgr);
+
+    client.timelock_delay_set(&100);
+    assert_eq!(client.try_sale_fee_set(&fee_mgr, &250), Err(Ok(Error::TimelockRequired)));
+    assert_eq!(
+        client.try_timelock_schedule(&fee_mgr, &TimelockChange::SaleFee(2_501)),
+        Err(Ok(Error::BadFee))
+    );
+    let id = client.timelock_schedule(&fee_mgr, &TimelockChange::SaleFee(250));
+    assert_eq!(client.try_timelock_execute(&id), Err(Ok(Error::TimelockNotReady)));
+    assert_eq!(client.sale_fee_get(), 0);
+
+    env.ledger().with_mut(|l| l.sequence_number += 100);
+    client.timelock_execute(&id);
+    assert_eq!(client.sale_fee_get(), 250);
+}
+
+#[test]
+fn test_nft_approvals_clear_on_transfer() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let operator = Address::generate(&env);
+    let id = client.mint_nft(&admin, &alice, &pixels(&env, 12));
+
+    assert_eq!(client.try_nft_transfer_from(&bob, &alice, &bob, &id), Err(Ok(Error::NotApproved)));
+    assert_eq!(client.try_nft_approve(&bob, &bob, &id, &100), Err(Ok(Error::NotOwner)));
+
+    client.nft_approve(&alice, &bob, &id, &100);
+    assert_eq!(client.nft_get_approved(&id), Some(bob.clone()));
+    client.nft_transfer_from(&bob, &alice, &bob, &id);
+    assert_eq!(client.nft_get(&id).0, bob);
+    assert_eq!(client.nft_get_approved(&id), None);
+
+    // Approval hết hạn
+    client.nft_approve(&bob, &alice, &id, &10);
+    env.ledger().with_mut(|l| l.sequence_number = 11);
+    assert_eq!(client.nft_get_approved(&id), None);
+    assert_eq!(client.try_nft_transfer_from(&alice, &bob, &alice, &id), Err(Ok(Error::NotApproved)));
+
+    client.nft_set_approval_for_all(&bob, &operator, &true);
+    assert!(client.nft_is_approved_for_all(&bob, &operator));
+    client.nft_transfer_from(&operator, &bob, &alice, &id);
+    assert_eq!(client.nft_ids_of(&alice), vec![&env, id]);
+
+    // Operator của chủ cũ không còn quyền
+    assert_eq!(client.try_nft_transfer_from(&operator, &alice, &bob, &id), Err(Ok(Error::NotApproved)));
+    client.nft_set_approval_for_all(&bob, &operator, &false);
+    assert!(!client.nft_is_approved_for_all(&bob, &operator));
+}
+
+#[test]
+fn test_nft_approve_rejects_expiration_beyond_max_ttl() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let id = client.mint_nft(&admin, &alice, &pixels(&env, 16));
+    let max_ttl = env.as_contract(&client.address, || env.storage().max_ttl());
+    let seq = env.ledger().sequence();
+
+    assert_eq!(
+        client.try_nft_approve(&alice, &bob, &id, &(seq + max_ttl + 1)),
+        Err(Ok(Error::BadExpirationLedger))
+    );
+    client.nft_approve(&alice, &bob, &id, &(seq + max_ttl));
+    assert_eq!(client.nft_get_approved(&id), Some(bob));
+}
+
+#[test]
+fn test_nft_burn_frees_pattern_without_reusing_id() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let art = pixels(&env, 13);
+    client.transfer(&admin, &alice, &1_000);
+
+    let id0 = client.mint_nft(&admin, &alice, &art);
+    client.nft_index_range(&admin, &id0, &0, &27);
+    client.market_list_nft(&alice, &id0, &50, &None);
+    assert_eq!(client.try_nft_burn(&admin, &id0), Err(Ok(Error::NotOwner)));
+
+    client.nft_burn(&alice, &id0);
+    assert_eq!(client.nft_total(), 0);
+    assert_eq!(client.try_nft_get(&id0), Err(Ok(Error::NftNotFound)));
+    assert_eq!(client.nft_find_by_value(&art), None);
+    assert_eq!(client.nft_ids_of(&alice), Vec::new(&env));
+    assert_eq!(client.market_list_ids_page(&0, &10), Vec::new(&env));
+    assert_eq!(client.market_get(&id0), None);
+    let col: u32 = art.get_unchecked(0).into();
+    assert_eq!(client.nft_search_pos_color(&0, &col), Vec::new(&env));
+    env.as_contract(&client.address, || {
+        assert!(!env.storage().persistent().has(&(IDX_MASK, id0)));
+    });
+
+    // Pattern dùng lại được, id thì không
+    let id1 = client.mint_nft(&admin, &alice, &art);
+    assert_eq!(id1, id0 + 1);
+    client.nft_admin_burn(&id1);
+    assert_eq!(client.nft_total(), 0);
+    assert_eq!(client.try_nft_admin_burn(&id1), Err(Ok(Error::NftNotFound)));
+}
+
+#[test]
+fn test_burn_after_migrate_clears_legacy_index() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let old_art = pixels(&env, 14);
+    let id0 = client.mint_nft(&admin, &alice, &old_art);
+
+    // NFT mint trước v4 không có IDX_MASK
+    env.as_contract(&client.address, || {
+        env.storage().persistent().remove(&(IDX_MASK, id0));
+        env.storage().instance().set(&VERSION, &3u32);
+    });
+    assert_eq!(client.migrate(), SCHEMA_VERSION);
+    let new_art = pixels(&env, 15);
+    let id1 = client.mint_nft(&admin, &alice, &new_art);
+
+    client.nft_burn(&alice, &id0);
+    client.nft_burn(&alice, &id1);
+    for i in 0..81u32 {
+        let col: u32 = old_art.get_unchecked(i).into();
+        assert_eq!(client.nft_search_pos_color(&i, &col), Vec::new(&env));
+        let col: u32 = new_art.get_unchecked(i).into();
+        assert_eq!(client.nft_search_pos_color(&i, &col), Vec::new(&env));
+    }
+}
+
+#[test]
+fn test_mint_public_charges_price_and_enforces_limits() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let treasury = Address::generate(&env);
+    let artist = Address::generate(&env);
+    client.treasury_set(&treasury);
+    client.transfer(&admin, &artist, &1_000);
+    let treasury_bal = client.balance(&treasury);
+    client.mint_commit_required_set(&admin, &false);
+
+    assert_eq!(client.try_mint_public(&artist, &pixels(&env, 1)), Err(Ok(Error::MintClosed)));
+
+    let cfg = PublicMintConfig { enabled: true, price: 100, wallet_limit: 2, start_ledger: 10, end_ledger: 20 };
+    client.public_mint_set(&admin, &cfg);
+    assert_eq!(client.try_mint_public(&artist, &pixels(&env, 1)), Err(Ok(Error::MintNotStarted)));
+
+    env.ledger().with_mut(|l| l.sequence_number = 10);
+    let id = client.mint_public(&artist, &pixels(&env, 1));
+    assert_eq!(client.nft_get(&id), (artist.clone(), pixels(&env, 1)));
+    assert_eq!(client.balance(&artist), 900);
+    assert_eq!(client.balance(&treasury), treasury_bal + 100);
+    assert_eq!(client.try_mint_public(&artist, &pixels(&env, 1)), Err(Ok(Error::DuplicateValue)));
+
+    client.mint_public(&artist, &pixels(&env, 2));
+    assert_eq!(client.public_minted_of(&artist), 2);
+    assert_eq!(client.try_mint_public(&artist, &pixels(&env, 3)), Err(Ok(Error::MintLimitReached)));
+
+    let poor = Address::generate(&env);
+    assert_eq!(client.try_mint_public(&poor, &pixels(&env, 3)), Err(Ok(Error::InsufficientBalance)));
+    env.ledger().with_mut(|l| l.sequence_number = 21);
+    assert_eq!(client.try_mint_public(&poor, &pixels(&env, 3)), Err(Ok(Error::MintEnded)));
+}
+
+fn allowlist_leaf(env: &Env, to: &Address, allowance: u32) -> BytesN<32> {
+    let mut leaf = to.clone().to_xdr(env);
+    leaf.extend_from_array(&allowance.to_be_bytes());
+    env.crypto().sha256(&leaf).into()
+}
+
+#[test]
+fn test_mint_allowlisted_verifies_proof_and_quota() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let mallory = Address::generate(&env);
+
+    let leaf_a = allowlist_leaf(&env, &alice, 1);
+    let leaf_b = allowlist_leaf(&env, &bob, 2);
+    let root = hash_pair(&env, &leaf_a, &leaf_b);
+    assert_eq!(
+        client.try_mint_allowlisted(&alice, &pixels(&env, 1), &1, &vec![&env, leaf_b.clone()]),
+        Err(Ok(Error::CommitRequired))
+    );
+    client.mint_commit_required_set(&admin, &false);
+
+    assert_eq!(
+        client.try_mint_allowlisted(&alice, &pixels(&env, 1), &1, &vec![&env, leaf_b.clone()]),
+        Err(Ok(Error::MintClosed))
+    );
+    client.allowlist_set(&admin, &Some(AllowlistConfig { root, price: 0 }));
+
+    client.mint_allowlisted(&alice, &pixels(&env, 1), &1, &vec![&env, leaf_b.clone()]);
+    assert_eq!(client.allowlist_minted_of(&alice), 1);
+    assert_eq!(
+        client.try_mint_allowlisted(&alice, &pixels(&env, 2), &1, &vec![&env, leaf_b.clone()]),
+        Err(Ok(Error::MintLimitReached))
+    );
+    // Khai tăng allowance → proof sai
+    assert_eq!(
+        client.try_mint_allowlisted(&alice, &pixels(&env, 2), &5, &vec![&env, leaf_b.clone()]),
+        Err(Ok(Error::InvalidProof))
+    );
+    assert_eq!(
+        client.try_mint_allowlisted(&mallory, &pixels(&env, 2), &2, &vec![&env, leaf_a.clone()]),
+        Err(Ok(Error::InvalidProof))
+    );
+
+    client.mint_allowlisted(&bob, &pixels(&env, 2), &2, &vec![&env, leaf_a.clone()]);
+    client.mint_allowlisted(&bob, &pixels(&env, 3), &2, &vec![&env, leaf_a]);
+    assert_eq!(client.nft_ids_of(&bob).len(), 2);
+
+    client.allowlist_set(&admin, &None);
+    assert_eq!(client.allowlist_get(), None);
+}
+
+#[test]
+fn test_mint_commit_reveal_and_refund() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let treasury = Address::generate(&env);
+    let artist = Address::generate(&env);
+    let sniper = Address::generate(&env);
+    client.treasury_set(&treasury);
+    client.transfer(&admin, &artist, &1_000);
+    client.transfer(&admin, &sniper, &1_000);
+    let treasury_bal = client.balance(&treasury);
+
+    let cfg = PublicMintConfig { enabled: true, price: 100, wallet_limit: 0, start_ledger: 0, end_ledger: 0 };
+    client.public_mint_set(&admin, &cfg);
+    assert!(client.mint_commit_required());
+    client.mint_commit_required_set(&admin, &true);
+    let ev = MintCommitRequiredSet { required: true };
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+    assert_eq!(client.try_mint_public(&sniper, &pixels(&env, 1)), Err(Ok(Error::CommitRequired)));
+
+    let salt = BytesN::from_array(&env, &[7; 32]);
+    let hash = commit_hash(&env, &artist, &pixels(&env, 1), &salt);
+    // Sniper chép hash từ mempool và commit trước → không chặn được commit của artist
+    client.mint_commit(&sniper, &hash);
+    client.mint_commit(&artist, &hash);
+    assert_eq!(client.balance(&artist), 900);
+    assert_eq!(client.balance(&client.address), 200);
+    assert_eq!(client.try_mint_reveal(&artist, &pixels(&env, 1), &salt), Err(Ok(Error::RevealTooEarly)));
+
+    // Copy payload nhưng hash gắn với creator → không có commit
+    env.ledger().with_mut(|l| l.sequence_number += 1);
+    assert_eq!(client.try_mint_reveal(&sniper, &pixels(&env, 1), &salt), Err(Ok(Error::CommitNotFound)));
+
+    let id = client.mint_reveal(&artist, &pixels(&env, 1), &salt);
+    assert_eq!(client.nft_get(&id).0, artist);
+    assert_eq!(client.balance(&treasury), treasury_bal + 100);
+    assert_eq!(client.balance(&client.address), 100);
+    assert_eq!(client.mint_commit_get(&artist, &hash), None);
+    assert!(client.mint_commit_get(&sniper, &hash).is_some());
+
+    // Commit quá hạn → hoàn cọc
+    let hash2 = commit_hash(&env, &artist, &pixels(&env, 2), &salt);
+    client.mint_commit(&artist, &hash2);
+    assert_eq!(client.try_mint_commit_refund(&artist, &hash2), Err(Ok(Error::CommitNotExpired)));
+    env.ledger().with_mut(|l| l.sequence_number += REVEAL_WINDOW + 1);
+    assert_eq!(client.try_mint_reveal(&artist, &pixels(&env, 2), &salt), Err(Ok(Error::RevealWindowClosed)));
+    assert_eq!(client.mint_commit_refund(&artist, &hash2), 100);
+    assert_eq!(client.balance(&artist), 900);
+}
+
+#[test]
+fn test_design_submission_approve_and_reject() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let artist = Address::generate(&env);
+    let copycat = Address::generate(&env);
+    client.transfer(&admin, &artist, &1_000);
+    client.transfer(&admin, &copycat, &1_000);
+    client.design_fee_set(&admin, &50);
+    let ev = DesignFeeSet { old_fee: 0, new_fee: 50 };
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+
+    let sub0 = client.submit_design(&artist, &pixels(&env, 1));
+    assert_eq!(client.balance(&artist), 950);
+    assert_eq!(client.try_submit_design(&copycat, &pixels(&env, 1)), Err(Ok(Error::DesignPending)));
+    let sub1 = client.submit_design(&artist, &pixels(&env, 2));
+    assert_eq!(client.design_pending_ids(), vec![&env, sub0, sub1]);
+
+    let nft_id = client.approve_design(&admin, &sub0);
+    assert_eq!(client.nft_get(&nft_id), (artist.clone(), pixels(&env, 1)));
+    assert_eq!(client.balance(&client.address), 50);
+    assert_eq!(client.try_submit_design(&copycat, &pixels(&env, 1)), Err(Ok(Error::DuplicateValue)));
+
+    client.reject_design(&admin, &sub1, &Some(3));
+    let ev = DesignRejected { id: sub1, reason: Some(3) };
+    assert_eq!(
+        env.events().all().slice(1..),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+    assert_eq!(client.balance(&artist), 950);
+    assert_eq!(client.design_get(&sub1), None);
+    assert_eq!(client.design_pending_ids(), Vec::new(&env));
+    assert_eq!(client.try_approve_design(&admin, &sub1), Err(Ok(Error::DesignNotFound)));
+
+    // Bị từ chối → pattern được nộp lại
+    client.submit_design(&copycat, &pixels(&env, 2));
+}
+
+#[test]
+fn test_pending_design_cannot_be_sniped() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let artist = Address::generate(&env);
+    let sniper = Address::generate(&env);
+    let cfg = PublicMintConfig { enabled: true, price: 0, wallet_limit: 0, start_ledger: 0, end_ledger: 0 };
+    client.public_mint_set(&admin, &cfg);
+
+    client.mint_commit_required_set(&admin, &false);
+
+    let sub = client.submit_design(&artist, &pixels(&env, 3));
+    // Pixels lộ trong event design_submitted, nhưng mọi đường mint khác đều bị chặn
+    assert_eq!(client.try_mint_public(&sniper, &pixels(&env, 3)), Err(Ok(Error::DesignPending)));
+    assert_eq!(client.try_mint_nft(&admin, &sniper, &pixels(&env, 3)), Err(Ok(Error::DesignPending)));
+
+    let nft_id = client.approve_design(&admin, &sub);
+    assert_eq!(client.nft_get(&nft_id).0, artist);
+}
+
+#[test]
+fn test_reveal_beats_later_design_submission() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let artist = Address::generate(&env);
+    let sniper = Address::generate(&env);
+    client.transfer(&admin, &sniper, &1_000);
+    client.design_fee_set(&admin, &50);
+    let cfg = PublicMintConfig { enabled: true, price: 0, wallet_limit: 0, start_ledger: 0, end_ledger: 0 };
+    client.public_mint_set(&admin, &cfg);
+
+    // Bài nộp trước commit vẫn giữ chỗ
+    let early = client.submit_design(&sniper, &pixels(&env, 4));
+    env.ledger().with_mut(|l| l.sequence_number += 1);
+    let salt = BytesN::from_array(&env, &[9; 32]);
+    client.mint_commit(&artist, &commit_hash(&env, &artist, &pixels(&env, 4), &salt));
+    client.mint_commit(&artist, &commit_hash(&env, &artist, &pixels(&env, 5), &salt));
+    env.ledger().with_mut(|l| l.sequence_number += REVEAL_MIN_DELAY);
+    assert_eq!(client.try_mint_reveal(&artist, &pixels(&env, 4), &salt), Err(Ok(Error::DesignPending)));
+
+    // Sniper copy pixels từ reveal đang chờ và submit_design trước → reveal vẫn thắng, sniper được hoàn cọc
+    let copy = client.submit_design(&sniper, &pixels(&env, 5));
+    assert_eq!(client.balance(&sniper), 1_000 - 100);
+    let id = client.mint_reveal(&artist, &pixels(&env, 5), &salt);
+    assert_eq!(client.nft_get(&id).0, artist);
+    assert_eq!(client.design_get(&copy), None);
+    assert_eq!(client.design_pending_ids(), vec![&env, early]);
+    assert_eq!(client.balance(&sniper), 1_000 - 50);
+}
+
+#[test]
+fn test_market_buy_pays_creator_royalty() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let artist = Address::generate(&env);
+    let studio = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    let buyer2 = Address::generate(&env);
+    client.transfer(&admin, &buyer, &2_000);
+    client.transfer(&admin, &buyer2, &2_000);
+    client.listing_fee_set(&admin, &0);
+
+    assert_eq!(client.try_royalty_default_set(&admin, &(MAX_ROYALTY_BPS + 1)), Err(Ok(Error::BadRoyalty)));
+    client.royalty_default_set(&admin, &500);
+    let id = client.mint_nft(&admin, &artist, &pixels(&env, 21));
+    assert_eq!(client.royalty_info(&id, &1_000), Some((artist.clone(), 50)));
+
+    // Bán lần đầu: artist vừa là seller vừa là creator
+    client.market_list_nft(&artist, &id, &1_000, &None);
+    client.market_buy(&buyer, &id);
+    assert_eq!(client.balance(&artist), 1_000);
+
+    assert_eq!(client.try_royalty_recipient_set(&buyer, &id, &buyer), Err(Ok(Error::NotCreator)));
+    client.royalty_recipient_set(&artist, &id, &studio);
+
+    let buyer_bal = client.balance(&buyer);
+    client.market_list_nft(&buyer, &id, &1_000, &None);
+    client.market_buy(&buyer2, &id);
+    assert_eq!(client.balance(&studio), 50);
+    assert_eq!(client.balance(&buyer), buyer_bal + 950);
+    assert_eq!(client.balance(&buyer2), 1_000);
+}
+
+#[test]
+fn test_market_quote_and_protocol_sale_fee() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let treasury = Address::generate(&env);
+    let artist = Address::generate(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    client.treasury_set(&treasury);
+    client.listing_fee_set(&admin, &0);
+    client.transfer(&admin, &buyer, &5_000);
+
+    client.royalty_default_set(&admin, &1_000);
+    let id = client.mint_nft(&admin, &artist, &pixels(&env, 22));
+    client.nft_transfer(&artist, &seller, &id);
+    assert_eq!(client.try_sale_fee_set(&admin, &(MAX_SALE_FEE_BPS + 1)), Err(Ok(Error::BadFee)));
+    client.sale_fee_set(&admin, &250);
+    client.market_list_nft(&seller, &id, &2_000, &None);
+
+    let quote = client.market_quote_buy(&id);
+    assert_eq!(
+        quote,
+        MarketQuote {
+            price: 2_000,
+            protocol_fee: 50,
+            royalty: 200,
+            royalty_recipient: Some(artist.clone()),
+            seller_net: 1_750,
+        }
+    );
+
+    let treasury_bal = client.balance(&treasury);
+    client.market_buy(&buyer, &id);
+    assert_eq!(client.balance(&seller), 1_750);
+    assert_eq!(client.balance(&artist), 200);
+    assert_eq!(client.balance(&treasury), treasury_bal + 50);
+    assert_eq!(client.balance(&buyer), 3_000);
+    assert_eq!(client.try_market_quote_buy(&id), Err(Ok(Error::NotListed)));
+}
+
+#[test]
+fn test_listing_expiry_hides_and_prunes() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    client.listing_fee_set(&admin, &0);
+    client.transfer(&admin, &buyer, &1_000);
+    let id0 = client.mint_nft(&admin, &seller, &pixels(&env, 30));
+    let id1 = client.mint_nft(&admin, &seller, &pixels(&env, 31));
+    let id2 = client.mint_nft(&admin, &seller, &pixels(&env, 32));
+
+    env.ledger().with_mut(|l| l.sequence_number = 100);
+    assert_eq!(
+        client.try_market_list_nft(&seller, &id0, &10, &Some(99)),
+        Err(Ok(Error::BadExpirationLedger))
+    );
+    client.market_list_nft(&seller, &id0, &10, &Some(110));
+    client.market_list_nft(&seller, &id1, &20, &Some(120));
+    client.market_list_nft(&seller, &id2, &30, &None);
+    assert_eq!(client.market_listing(&id0).unwrap().expires_ledger, Some(110));
+
+    env.ledger().with_mut(|l| l.sequence_number = 115);
+    assert_eq!(client.market_list_count(), 3);
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id1, id2]);
+    assert_eq!(client.market_list_ids_page(&2, &1), vec![&env, id2]);
+    assert_eq!(client.market_get(&id0), None);
+    assert_eq!(client.try_market_buy(&buyer, &id0), Err(Ok(Error::ListingExpired)));
+
+    // Listing hết hạn không chặn chuyển NFT
+    client.nft_transfer(&seller, &buyer, &id0);
+    assert_eq!(client.market_list_count(), 2);
+
+    // Mỗi lần chỉ quét `max` listing từ con trỏ, con trỏ quay về 0 khi hết LIDS
+    assert_eq!(client.market_prune_expired(&1), 0);
+    env.ledger().with_mut(|l| l.sequence_number = 121);
+    assert_eq!(client.market_prune_expired(&1), 0);
+    assert_eq!(client.market_prune_expired(&1), 1);
+    assert_eq!(client.market_prune_expired(&10), 0);
+    assert_eq!(client.market_list_count(), 1);
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id2]);
+    env.as_contract(&client.address, || {
+        let ids: Vec<u32> = env.storage().persistent().get(&LIDS).unwrap();
+        assert_eq!(ids, vec![&env, id2]);
+        assert!(!env.storage().persistent().has(&(LST, id1)));
+    });
+    client.market_buy(&buyer, &id2);
+}
+
+#[test]
+fn test_market_update_price_keeps_position_with_cooldown() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    client.listing_fee_set(&admin, &0);
+    client.market_price_cooldown_set(&admin, &50);
+    let ev = PriceCooldownSet { old: 0, new: 50 };
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+    client.transfer(&admin, &buyer, &1_000);
+    let id0 = client.mint_nft(&admin, &seller, &pixels(&env, 40));
+    let id1 = client.mint_nft(&admin, &seller, &pixels(&env, 41));
+    client.market_list_nft(&seller, &id0, &100, &None);
+    client.market_list_nft(&seller, &id1, &200, &None);
+
+    assert_eq!(client.try_market_update_price(&buyer, &id0, &90), Err(Ok(Error::NotSeller)));
+    client.market_update_price(&seller, &id0, &90);
+    let ev = MarketPriceChanged { id: id0, seller: seller.clone(), old_price: 100, new_price: 90 };
+    assert_eq!(
+        env.events().all(),
+        vec![&env, (client.address.clone(), ev.topics(&env), ev.data(&env))]
+    );
+    assert_eq!(client.market_list_ids_page(&0, &10), vec![&env, id0, id1]);
+
+    assert_eq!(client.try_market_update_price(&seller, &id0, &500), Err(Ok(Error::PriceCooldown)));
+    env.ledger().with_mut(|l| l.sequence_number += 50);
+    client.market_update_price(&seller, &id0, &500);
+    assert_eq!(client.market_get(&id0), Some((seller.clone(), 500)));
+
+    client.market_buy(&buyer, &id0);
+    assert_eq!(client.balance(&buyer), 500);
+}
+
+#[test]
+fn test_market_buy_checked_max_price_and_nonce() {
+    let env = Env::default();
+    let (client, admin) = setup(&env);
+    let seller = Address::generate(&env);
+    let buyer = Address::generate(&env);
+    client.listing_fee_set(&admin, &0);
+    client.transfer(&admin, &buyer, &1_000);
+    let id = client.mint_nft(&admin, &seller, &pixels(&env, 42));
+    assert_eq!(client.market_listing_nonce(&id), 0);
+    client.market_list_nft(&seller, &id, &100, &None);
+    let seen = client.market_listing_nonce(&id);
+    assert_eq!(seen, 1);
+
+    // Seller hủy rồi niêm yết lại với giá cao hơn → nonce đổi
+    client.market_cancel(&seller, &id);
+    client.market_list_nft(&seller, &id, &300, &None);
+    assert_eq!(client.market_listing_nonce(&id), 2);
+    assert_eq!(client.try_market_buy_checked(&buyer, &id, &100, &None), Err(Ok(Error::PriceAboveMax)));
+    assert_eq!(client.try_market_buy_checked(&buyer, &id, &300, &Some(seen)), Err(Ok(Error::ListingNonceMismatch)));
+
+    client.market_update_price(&seller, &id, &250);
+    assert_eq!(client.market_listing_nonce(&id), 3);
+    client.market_buy_checked(&buyer, &id, &300, &Some(3));
+    assert_eq!(client.balance(&buyer), 750);
+    assert_eq!(client.nft_get(&id).0, buyer);
+}
@@ -5,8 +5,8 @@ import {
   tokenTransfer,
   readPalette, readNftValue, readNftIdsOf,
   nftTransfer,
-  marketListIds, marketGet, marketList, marketCancel, marketBuy,
-  mintNftFlex, parsePixelsFlex9x9
+  marketActiveListIds, marketGet, marketList, marketCancel, marketBuy,
+  mintNftFlex, mintPublicFlex, parsePixelsFlex9x9
 } from "./stellar";
 import { drawNftToCanvas } from "./nftRender";
 
@@ -316,7 +316,7 @@ export default function App() {
   const refreshListings = async () => {
     if (!pk) { notify.warn("Chưa kết nối ví."); return; }
     try {
-      const ids = await marketListIds(pk) as number[];
+      const ids = await marketActiveListIds(pk);
       setListIds(ids || []);
       notify.info(`Đã tải danh sách đang bán: ${ids?.length || 0} item(s).`);
     } catch (e:any) {
@@ -350,6 +350,20 @@ export default function App() {
     }
   };
 
+  const doMintPublic = async () => {
+    if (!pk) { notify.warn("Chưa kết nối ví."); return; }
+    try {
+      const res = await mintPublicFlex(pk, mintPixels);
+      notify.ok("Mint công khai thành công:\n" + JSON.stringify(res));
+      try {
+        const ids = await readNftIdsOf(pk, pk) as number[];
+        setMyIds(ids || []);
+      } catch {}
+    } catch (e:any) {
+      notify.err("Mint công khai thất bại:\n" + (e.message || String(e)));
+    }
+  };
+
   const doList = async (idStr: string, priceStr: string) => {
     if (!pk) { notify.warn("Chưa kết nối ví."); return; }
     try {
@@ -685,7 +699,7 @@ GAMY3VPG5SFCXM26AA5AMLJKN64Y7GJUNVRUO5VFKHV6OZGVS76YVJBH	150000
         </Section>
 
         {/* ===== Mint ===== */}
-        <Section title="Mint NFT (Admin) — 9×9×32">
+        <Section title="Mint NFT — 9×9×32">
           <div style={{ display:"grid", gridTemplateColumns:"1fr 240px", gap:12, alignItems:"start" }}>
             <div style={{ display:"grid", gap:8 }}>
               <textarea
@@ -696,7 +710,8 @@ GAMY3VPG5SFCXM26AA5AMLJKN64Y7GJUNVRUO5VFKHV6OZGVS76YVJBH	150000
               />
               <div style={{ display:"flex", gap:8, flexWrap:"wrap" }}>
                 <button style={{ ...btn, background:"#7c4dff" }} onClick={previewMint}>👀 Xem trước</button>
-                <button style={{ ...btn, background:"#ff8c00" }} onClick={doMint}>🧱 Mint</button>
+                <button style={{ ...btn, background:"#ff8c00" }} onClick={doMint}>🧱 Mint (Admin)</button>
+                <button style={{ ...btn, background:"#00a86b" }} onClick={doMintPublic}>🎨 Mint công khai (trả phí)</button>
               </div>
             </div>
             <div style={{ display:"grid", gap:8 }}>
@@ -7,6 +7,7 @@ import {
   rpc,
   scValToNative,
   TransactionBuilder,
+  xdr,
 } from "@stellar/stellar-sdk";
 import {
   isConnected,
@@ -142,6 +143,128 @@ export async function assertNetwork() {
   }
 }
 
+// ---------- contract errors ----------
+/** Mã lỗi #[contracterror] của contract (giữ đồng bộ với enum Error trong lib.rs) */
+export const CONTRACT_ERRORS: Record<number, string> = {
+  1: "ALREADY_INIT",
+  2: "NOT_INITED",
+  3: "BAD_DECIMALS",
+  4: "SUPPLY_OVERFLOW",
+  5: "BAD_FEE_CONFIG",
+  6: "BAD_SUPPLY",
+  7: "ALLOCATION_EXCEEDS_SUPPLY",
+  8: "MAX_SUPPLY_EXCEEDED",
+  9: "MIGRATION_REQUIRED",
+
+  10: "BAD_AMOUNT",
+  11: "BAD_AMOUNT_AT",
+  12: "SELF_TRANSFER",
+  13: "SELF_TRANSFER_AT",
+  14: "EMPTY_BATCH",
+  15: "LENGTH_MISMATCH",
+  16: "INSUFFICIENT_BALANCE",
+  17: "INSUFFICIENT_BALANCE_WITH_FEE",
+  18: "INSUFFICIENT_ALLOWANCE",
+  19: "BAD_EXPIRATION_LEDGER",
+  20: "BAL_OVERFLOW",
+  21: "FEE_ADD_OVERFLOW",
+  22: "FEE_MUL_OVERFLOW",
+  23: "AMOUNT_SUM_OVERFLOW",
+  24: "TOTAL_DEDUCT_OVERFLOW",
+
+  30: "PIXELS_LEN_81",
+  31: "PIXEL_OUT_OF_RANGE",
+  32: "DUPLICATE_VALUE",
+  33: "NFT_MAX_SUP_REACHED",
+  34: "NFT_ID_EXHAUSTED",
+  35: "NFT_NOT_FOUND",
+  36: "NOT_OWNER",
+  37: "OWNERSHIP_CORRUPTED",
+  38: "BAD_RANGE",
+  39: "POS_RANGE_0_80",
+  40: "COLOR_RANGE_0_31",
+  41: "PALETTE_32_REQUIRED",
+  42: "NO_PALETTE",
+  43: "NOT_APPROVED",
+  44: "NOT_CREATOR",
+  45: "BAD_ROYALTY",
+
+  50: "BAD_FEE",
+  51: "BAD_PRICE",
+  52: "ALREADY_LISTED",
+  53: "LISTED",
+  54: "NOT_LISTED",
+  55: "NOT_SELLER",
+  56: "LISTING_OWNER_MISMATCH",
+  57: "SELF_BUY",
+  58: "INSUFFICIENT_FOR_FEE",
+  59: "LISTING_EXPIRED",
+
+  60: "NO_PENDING_ADMIN",
+  61: "MISSING_ROLE",
+  62: "PAUSED",
+  63: "BAD_TTL_CONFIG",
+
+  70: "NO_COUNCIL",
+  71: "BAD_COUNCIL",
+  72: "NOT_COUNCIL_MEMBER",
+  73: "PROPOSAL_NOT_FOUND",
+  74: "PROPOSAL_EXPIRED",
+  75: "ALREADY_APPROVED",
+  76: "PROPOSAL_EXECUTED",
+  77: "NOT_ENOUGH_APPROVALS",
+  78: "COUNCIL_REQUIRED",
+
+  80: "TIMELOCK_REQUIRED",
+  81: "TIMELOCK_NOT_FOUND",
+  82: "TIMELOCK_NOT_READY",
+  83: "BAD_TIMELOCK_DELAY",
+
+  90: "MINT_CLOSED",
+  91: "MINT_NOT_STARTED",
+  92: "MINT_ENDED",
+  93: "MINT_LIMIT_REACHED",
+  94: "BAD_MINT_CONFIG",
+  95: "INVALID_PROOF",
+  96: "COMMIT_REQUIRED",
+  97: "COMMIT_NOT_FOUND",
+  98: "COMMIT_EXISTS",
+  99: "REVEAL_TOO_EARLY",
+  100: "REVEAL_WINDOW_CLOSED",
+  101: "COMMIT_NOT_EXPIRED",
+  102: "DESIGN_NOT_FOUND",
+  103: "DESIGN_PENDING",
+
+  110: "PRICE_COOLDOWN",
+  111: "PRICE_ABOVE_MAX",
+  112: "LISTING_NONCE_MISMATCH",
+};
+
+export class ContractError extends Error {
+  readonly code: number;
+  constructor(code: number) {
+    super(CONTRACT_ERRORS[code] ?? `CONTRACT_ERROR_${code}`);
+    this.name = "ContractError";
+    this.code = code;
+  }
+}
+
+/** Tìm "Error(Contract, #N)" trong lỗi simulate → ContractError(N) */
+function decodeContractError(raw: unknown): Error {
+  const msg = raw instanceof Error ? raw.message : String(raw);
+  const m = /Error\(Contract, #(\d+)\)/.exec(msg);
+  if (m) return new ContractError(Number(m[1]));
+  return raw instanceof Error ? raw : new Error(msg);
+}
+
+async function prepare(tx: ReturnType<TransactionBuilder["build"]>) {
+  try {
+    return await server.prepareTransaction(tx);
+  } catch (e) {
+    throw decodeContractError(e);
+  }
+}
+
 // ---------- low-level ----------
 async function simulateCall(source: string, method: string, params: any[] = []) {
   const sourceAccount = await server.getAccount(source);
@@ -153,9 +276,11 @@ async function simulateCall(source: string, method: string, params: any[] = [])
     .setTimeout(30)
     .build();
 
-  const prepared = await server.prepareTransaction(tx);
+  const prepared = await prepare(tx);
   const sim = await server.simulateTransaction(prepared);
-  if (!rpc.Api.isSimulationSuccess(sim)) throw new Error("Simulation failed");
+  if (!rpc.Api.isSimulationSuccess(sim)) {
+    throw decodeContractError((sim as any).error ?? "Simulation failed");
+  }
   const res = sim.result?.retval;
   return res ? scValToNative(res) : null;
 }
@@ -170,7 +295,7 @@ async function invokeWrite(source: string, method: string, params: any[] = []) {
     .setTimeout(120)
     .build();
 
-  const prepared = await server.prepareTransaction(tx);
+  const prepared = await prepare(tx);
   const signedRes = await signTransaction(prepared.toXDR(), {
     networkPassphrase: NETWORK_PASSPHRASE,
     address: source,
@@ -204,9 +329,39 @@ export const readName        = (src: string) => simulateCall(src, "name");
 export const readSymbol      = (src: string) => simulateCall(src, "symbol");
 export const readDecimals    = (src: string) => simulateCall(src, "decimals");
 export const readTotalSupply = (src: string) => simulateCall(src, "total_supply");
+export const readMaxSupply   = (src: string) => simulateCall(src, "max_supply"); // Option<i128>
 export const readBalanceOf   = (src: string, of: string) =>
   simulateCall(src, "balance_of", [Address.fromString(of).toScVal()]);
 
+export const readTransferFeeConfig = (src: string) => simulateCall(src, "transfer_fee_get");
+/** mode 0 = Flat (raw / giao dịch), 1 = Bps (0..10_000); min/max tuỳ chọn */
+export type TransferFeeInput = { mode: 0 | 1; value: bigint; minFee?: bigint; maxFee?: bigint };
+const optI128 = (v?: bigint) => (v === undefined ? xdr.ScVal.scvVoid() : nativeToScVal(v, { type: "i128" }));
+// Struct TransferFeeConfig → ScMap, key theo thứ tự chữ cái
+const transferFeeScVal = (cfg: TransferFeeInput) =>
+  xdr.ScVal.scvMap([
+    new xdr.ScMapEntry({ key: xdr.ScVal.scvSymbol("max_fee"), val: optI128(cfg.maxFee) }),
+    new xdr.ScMapEntry({ key: xdr.ScVal.scvSymbol("min_fee"), val: optI128(cfg.minFee) }),
+    new xdr.ScMapEntry({ key: xdr.ScVal.scvSymbol("mode"), val: nativeToScVal(cfg.mode, { type: "u32" }) }),
+    new xdr.ScMapEntry({ key: xdr.ScVal.scvSymbol("value"), val: nativeToScVal(cfg.value, { type: "i128" }) }),
+  ]);
+/** Khi bật timelock trả TIMELOCK_REQUIRED → dùng timelockSchedule({ kind: "TransferFee" }) */
+export const transferFeeSet = (src: string, cfg: TransferFeeInput) =>
+  invokeWrite(src, "transfer_fee_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(cfg.mode, { type: "u32" }),
+    nativeToScVal(cfg.value, { type: "i128" }),
+    optI128(cfg.minFee),
+    optI128(cfg.maxFee),
+  ]);
+export const readTreasury = (src: string) => simulateCall(src, "treasury_get");
+/** Phí sẽ bị trừ thêm (raw) khi chuyển `amount` */
+export const readTransferFeeQuote = (src: string, amount: bigint) =>
+  simulateCall(src, "transfer_fee_quote", [nativeToScVal(amount, { type: "i128" })]);
+
+export const readFeeExempt = (src: string, account: string) =>
+  simulateCall(src, "fee_exempt_get", [Address.fromString(account).toScVal()]);
+
 export const tokenTransfer = (src: string, from: string, to: string, amount: bigint) =>
   invokeWrite(src, "transfer", [
     Address.fromString(from).toScVal(),
@@ -248,10 +403,46 @@ export const nftTransfer = (src: string, from: string, to: string, id: number) =
     nativeToScVal(id, { type: "u32" }),
   ]);
 
+export const nftTransferFrom = (src: string, from: string, to: string, id: number) =>
+  invokeWrite(src, "nft_transfer_from", [
+    Address.fromString(src).toScVal(),
+    Address.fromString(from).toScVal(),
+    Address.fromString(to).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+  ]);
+
+export const nftBurn = (src: string, id: number) =>
+  invokeWrite(src, "nft_burn", [Address.fromString(src).toScVal(), nativeToScVal(id, { type: "u32" })]);
+export const nftAdminBurn = (src: string, id: number) =>
+  invokeWrite(src, "nft_admin_burn", [nativeToScVal(id, { type: "u32" })]);
+
+/** expirationLedger = 0 → thu hồi approval */
+export const nftApprove = (src: string, spender: string, id: number, expirationLedger: number) =>
+  invokeWrite(src, "nft_approve", [
+    Address.fromString(src).toScVal(),
+    Address.fromString(spender).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+    nativeToScVal(expirationLedger, { type: "u32" }),
+  ]);
+export const nftSetApprovalForAll = (src: string, operator: string, approved: boolean) =>
+  invokeWrite(src, "nft_set_approval_for_all", [
+    Address.fromString(src).toScVal(),
+    Address.fromString(operator).toScVal(),
+    nativeToScVal(approved, { type: "bool" }),
+  ]);
+export const readNftApproved = (src: string, id: number) =>
+  simulateCall(src, "nft_get_approved", [nativeToScVal(id, { type: "u32" })]); // Option<Address>
+export const readNftApprovedForAll = (src: string, owner: string, operator: string) =>
+  simulateCall(src, "nft_is_approved_for_all", [
+    Address.fromString(owner).toScVal(),
+    Address.fromString(operator).toScVal(),
+  ]);
+
 /** Mint 9×9: pixels phải là Uint8Array dài 81, mỗi phần tử 0..31 */
 export const mintNft = (src: string, to: string, pixels: Uint8Array) => {
   assertPixels9x9(pixels);
   return invokeWrite(src, "mint_nft", [
+    Address.fromString(src).toScVal(), // minter (admin hoặc Role::Minter)
     Address.fromString(to).toScVal(),
     nativeToScVal(pixels, { type: "bytes" }),
   ]);
@@ -267,11 +458,195 @@ export const mintNftFlex = (
   return mintNft(src, to, pixels);
 };
 
+/** Mint công khai cho chính ví src, trả giá FT theo public_mint_get */
+export const mintPublic = (src: string, pixels: Uint8Array) => {
+  assertPixels9x9(pixels);
+  return invokeWrite(src, "mint_public", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(pixels, { type: "bytes" }),
+  ]);
+};
+export const mintPublicFlex = (src: string, pixelsInput: string | number[] | Uint8Array) =>
+  mintPublic(src, parsePixelsFlex9x9(pixelsInput));
+/** { enabled, price, wallet_limit, start_ledger, end_ledger } */
+export const readPublicMint = (src: string) => simulateCall(src, "public_mint_get");
+export const readPublicMintedOf = (src: string, creator: string) =>
+  simulateCall(src, "public_minted_of", [Address.fromString(creator).toScVal()]);
+export const publicMintSet = (
+  src: string,
+  cfg: { enabled: boolean; price: bigint; walletLimit: number; startLedger: number; endLedger: number }
+) =>
+  invokeWrite(src, "public_mint_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(
+      {
+        enabled: cfg.enabled,
+        end_ledger: cfg.endLedger,
+        price: cfg.price,
+        start_ledger: cfg.startLedger,
+        wallet_limit: cfg.walletLimit,
+      },
+      {
+        type: {
+          enabled: ["symbol", "bool"],
+          end_ledger: ["symbol", "u32"],
+          price: ["symbol", "i128"],
+          start_ledger: ["symbol", "u32"],
+          wallet_limit: ["symbol", "u32"],
+        },
+      }
+    ),
+  ]);
+
+// ---- Presale allowlist (Merkle) ----
+const sha256 = async (data: Uint8Array) =>
+  new Uint8Array(await crypto.subtle.digest("SHA-256", data as BufferSource));
+
+function concatBytes(a: Uint8Array, b: Uint8Array) {
+  const out = new Uint8Array(a.length + b.length);
+  out.set(a, 0);
+  out.set(b, a.length);
+  return out;
+}
+
+function compareBytes(a: Uint8Array, b: Uint8Array) {
+  for (let i = 0; i < a.length; i++) if (a[i] !== b[i]) return a[i] - b[i];
+  return 0;
+}
+
+/** leaf = sha256(xdr(Address) ‖ allowance u32 big-endian) — khớp contract */
+export async function allowlistLeaf(address: string, allowance: number) {
+  const addr = new Uint8Array(Address.fromString(address).toScVal().toXDR());
+  const n = new Uint8Array(4);
+  new DataView(n.buffer).setUint32(0, allowance, false);
+  return sha256(concatBytes(addr, n));
+}
+
+const hashPair = (a: Uint8Array, b: Uint8Array) =>
+  sha256(compareBytes(a, b) <= 0 ? concatBytes(a, b) : concatBytes(b, a));
+
+/** Dựng cây Merkle từ danh sách allowlist → root + proof cho từng địa chỉ */
+export async function buildAllowlist(entries: { address: string; allowance: number }[]) {
+  if (entries.length === 0) throw new Error("EMPTY_ALLOWLIST");
+  const leaves = await Promise.all(entries.map((e) => allowlistLeaf(e.address, e.allowance)));
+  const proofs: Uint8Array[][] = entries.map(() => []);
+  let level = leaves.map((h, i) => ({ h, members: [i] }));
+  while (level.length > 1) {
+    const next: typeof level = [];
+    for (let i = 0; i < level.length; i += 2) {
+      const left = level[i];
+      const right = level[i + 1];
+      if (!right) { next.push(left); continue; } // node lẻ được đẩy lên nguyên
+      left.members.forEach((m) => proofs[m].push(right.h));
+      right.members.forEach((m) => proofs[m].push(left.h));
+      next.push({ h: await hashPair(left.h, right.h), members: [...left.members, ...right.members] });
+    }
+    level = next;
+  }
+  return { root: level[0].h, proofs };
+}
+
+// ---- Hàng đợi duyệt tranh ----
+export const submitDesign = (src: string, pixelsInput: string | number[] | Uint8Array) => {
+  const pixels = parsePixelsFlex9x9(pixelsInput);
+  return invokeWrite(src, "submit_design", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(pixels, { type: "bytes" }),
+  ]);
+};
+export const approveDesign = (src: string, id: number) =>
+  invokeWrite(src, "approve_design", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+  ]);
+/** reason: mã lý do tuỳ chọn (UI tự quy ước) */
+export const rejectDesign = (src: string, id: number, reason?: number) =>
+  invokeWrite(src, "reject_design", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+    reason === undefined ? xdr.ScVal.scvVoid() : nativeToScVal(reason, { type: "u32" }),
+  ]);
+export const readDesign = (src: string, id: number) =>
+  simulateCall(src, "design_get", [nativeToScVal(id, { type: "u32" })]);
+export const readDesignPendingIds = (src: string) => simulateCall(src, "design_pending_ids");
+export const readDesignFee = (src: string) => simulateCall(src, "design_fee_get");
+export const designFeeSet = (src: string, fee: bigint) =>
+  invokeWrite(src, "design_fee_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(fee, { type: "i128" }),
+  ]);
+
+// ---- Commit-reveal ----
+/** hash = sha256(xdr(creator) ‖ pixels ‖ salt) — khớp contract; giữ salt để reveal */
+export async function mintCommitHash(creator: string, pixels: Uint8Array, salt: Uint8Array) {
+  assertPixels9x9(pixels);
+  if (salt.length !== 32) throw new Error("SALT_32_BYTES");
+  const addr = new Uint8Array(Address.fromString(creator).toScVal().toXDR());
+  return sha256(concatBytes(concatBytes(addr, pixels), salt));
+}
+export const newMintSalt = () => crypto.getRandomValues(new Uint8Array(32));
+
+export const mintCommit = (src: string, hash: Uint8Array) =>
+  invokeWrite(src, "mint_commit", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(hash, { type: "bytes" }),
+  ]);
+export const mintReveal = (src: string, pixels: Uint8Array, salt: Uint8Array) => {
+  assertPixels9x9(pixels);
+  return invokeWrite(src, "mint_reveal", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(pixels, { type: "bytes" }),
+    nativeToScVal(salt, { type: "bytes" }),
+  ]);
+};
+export const mintCommitRefund = (src: string, hash: Uint8Array) =>
+  invokeWrite(src, "mint_commit_refund", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(hash, { type: "bytes" }),
+  ]);
+export const readMintCommit = (src: string, creator: string, hash: Uint8Array) =>
+  simulateCall(src, "mint_commit_get", [
+    Address.fromString(creator).toScVal(),
+    nativeToScVal(hash, { type: "bytes" }),
+  ]);
+/** Mặc định true: mintPublic / mintAllowlisted trả COMMIT_REQUIRED, dùng mintCommit → mintReveal */
+export const readMintCommitRequired = (src: string) => simulateCall(src, "mint_commit_required");
+export const mintCommitRequiredSet = (src: string, required: boolean) =>
+  invokeWrite(src, "mint_commit_required_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(required, { type: "bool" }),
+  ]);
+
+export const readAllowlist = (src: string) => simulateCall(src, "allowlist_get"); // Option<{ root, price }>
+export const readAllowlistMintedOf = (src: string, to: string) =>
+  simulateCall(src, "allowlist_minted_of", [Address.fromString(to).toScVal()]);
+/** root = null → đóng presale */
+export const allowlistSet = (src: string, root: Uint8Array | null, price: bigint = 0n) =>
+  invokeWrite(src, "allowlist_set", [
+    Address.fromString(src).toScVal(),
+    root === null
+      ? xdr.ScVal.scvVoid()
+      : nativeToScVal(
+          { price, root },
+          { type: { price: ["symbol", "i128"], root: ["symbol", "bytes"] } }
+        ),
+  ]);
+export const mintAllowlisted = (src: string, pixels: Uint8Array, allowance: number, proof: Uint8Array[]) => {
+  assertPixels9x9(pixels);
+  return invokeWrite(src, "mint_allowlisted", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(pixels, { type: "bytes" }),
+    nativeToScVal(allowance, { type: "u32" }),
+    xdr.ScVal.scvVec(proof.map((p) => nativeToScVal(p, { type: "bytes" }))),
+  ]);
+};
+
 /** Đánh chỉ mục theo lô cho id trong [start, end), ví dụ 0..27, 27..54, 54..81 */
 export const nftIndexRange = (src: string, id: number, start: number, end: number) => {
   if (start < 0 || end < 0 || start >= end) throw new Error("BAD_RANGE");
   if (end > NFT_PIXELS) throw new Error("BAD_RANGE");
   return invokeWrite(src, "nft_index_range", [
+    Address.fromString(src).toScVal(),
     nativeToScVal(id, { type: "u32" }),
     nativeToScVal(start, { type: "u32" }),
     nativeToScVal(end, { type: "u32" }),
@@ -286,7 +661,10 @@ function toVecU32ScVal(arr: number[]) {
 export const paletteSet = (src: string, newPalette: number[]) => {
   if (!Array.isArray(newPalette) || newPalette.length !== 32)
     throw new Error("PALETTE_32_REQUIRED");
-  return invokeWrite(src, "palette_set", [toVecU32ScVal(newPalette)]);
+  return invokeWrite(src, "palette_set", [
+    Address.fromString(src).toScVal(),
+    toVecU32ScVal(newPalette),
+  ]);
 };
 
 // ======================================
@@ -294,13 +672,31 @@ export const paletteSet = (src: string, newPalette: number[]) => {
 // ======================================
 export const listingFeeGet = (src: string) => simulateCall(src, "listing_fee_get");
 export const listingFeeSet = (src: string, fee: bigint) =>
-  invokeWrite(src, "listing_fee_set", [nativeToScVal(fee, { type: "i128" })]);
+  invokeWrite(src, "listing_fee_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(fee, { type: "i128" }),
+  ]);
 
-export const marketList = (src: string, seller: string, id: number, price: bigint) =>
+/** expiresLedger: listing hết hiệu lực sau ledger này (bỏ trống → không hết hạn) */
+export const marketList = (src: string, seller: string, id: number, price: bigint, expiresLedger?: number) =>
   invokeWrite(src, "market_list_nft", [
     Address.fromString(seller).toScVal(),
     nativeToScVal(id, { type: "u32" }),
     nativeToScVal(price, { type: "i128" }),
+    expiresLedger === undefined ? xdr.ScVal.scvVoid() : nativeToScVal(expiresLedger, { type: "u32" }),
+  ]);
+
+export const marketUpdatePrice = (src: string, seller: string, id: number, newPrice: bigint) =>
+  invokeWrite(src, "market_update_price", [
+    Address.fromString(seller).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+    nativeToScVal(newPrice, { type: "i128" }),
+  ]);
+export const readMarketPriceCooldown = (src: string) => simulateCall(src, "market_price_cooldown");
+export const marketPriceCooldownSet = (src: string, ledgers: number) =>
+  invokeWrite(src, "market_price_cooldown_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(ledgers, { type: "u32" }),
   ]);
 
 export const marketCancel = (src: string, seller: string, id: number) =>
@@ -314,10 +710,305 @@ export const marketBuy = (src: string, buyer: string, id: number) =>
     Address.fromString(buyer).toScVal(),
     nativeToScVal(id, { type: "u32" }),
   ]);
+/** Chỉ mua khi giá ≤ maxPrice; truyền nonce (readMarketListingNonce) để khớp đúng listing đã xem */
+export const marketBuyChecked = (src: string, buyer: string, id: number, maxPrice: bigint, nonce?: number) =>
+  invokeWrite(src, "market_buy_checked", [
+    Address.fromString(buyer).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+    nativeToScVal(maxPrice, { type: "i128" }),
+    nonce === undefined ? xdr.ScVal.scvVoid() : nativeToScVal(nonce, { type: "u32" }),
+  ]);
+export const readMarketListingNonce = (src: string, id: number) =>
+  simulateCall(src, "market_listing_nonce", [nativeToScVal(id, { type: "u32" })]);
 
 export const marketGet = (src: string, id: number) =>
   simulateCall(src, "market_get", [nativeToScVal(id, { type: "u32" })]); // Option<(seller, price)>
-export const marketListIds = (src: string) => simulateCall(src, "market_list_ids");
+/** Số phần tử trong LIDS (gồm cả listing hết hạn chưa prune) */
+export const marketListCount = (src: string) => simulateCall(src, "market_list_count");
+/** Listing còn hiệu lực trong LIDS[start, start + limit) */
+export const marketListIdsPage = (src: string, start: number, limit: number) =>
+  simulateCall(src, "market_list_ids_page", [
+    nativeToScVal(start, { type: "u32" }),
+    nativeToScVal(limit, { type: "u32" }),
+  ]);
+/** Duyệt LIDS theo trang để mỗi lần simulate đọc tối đa pageSize listing */
+export async function marketActiveListIds(src: string, pageSize = 50) {
+  const total = Number((await marketListCount(src)) ?? 0);
+  const out: number[] = [];
+  for (let start = 0; start < total; start += pageSize) {
+    out.push(...(((await marketListIdsPage(src, start, pageSize)) as number[]) || []));
+  }
+  return out;
+}
+/** Option<{ seller, price, expires_ledger }> — listing đã hết hạn trả về None */
+export const marketListing = (src: string, id: number) =>
+  simulateCall(src, "market_listing", [nativeToScVal(id, { type: "u32" })]);
+/** Ai cũng gọi được: quét tối đa `max` listing từ con trỏ của contract, dọn listing hết hạn; trả về số đã xoá */
+export const marketPruneExpired = (src: string, max: number) =>
+  invokeWrite(src, "market_prune_expired", [nativeToScVal(max, { type: "u32" })]);
+
+/** { price, protocol_fee, royalty, royalty_recipient, seller_net } */
+export const marketQuoteBuy = (src: string, id: number) =>
+  simulateCall(src, "market_quote_buy", [nativeToScVal(id, { type: "u32" })]);
+export const readSaleFee = (src: string) => simulateCall(src, "sale_fee_get"); // bps
+/** Khi bật timelock trả TIMELOCK_REQUIRED → dùng timelockSchedule({ kind: "SaleFee" }) */
+export const saleFeeSet = (src: string, bps: number) =>
+  invokeWrite(src, "sale_fee_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(bps, { type: "u32" }),
+  ]);
+
+// ---- Royalty ----
+export const readRoyalty = (src: string, id: number) =>
+  simulateCall(src, "royalty_get", [nativeToScVal(id, { type: "u32" })]); // Option<{ creator, recipient, bps }>
+/** Option<[recipient, amount]> cho giá bán salePrice */
+export const readRoyaltyInfo = (src: string, id: number, salePrice: bigint) =>
+  simulateCall(src, "royalty_info", [
+    nativeToScVal(id, { type: "u32" }),
+    nativeToScVal(salePrice, { type: "i128" }),
+  ]);
+export const readRoyaltyDefault = (src: string) => simulateCall(src, "royalty_default_get");
+export const royaltyDefaultSet = (src: string, bps: number) =>
+  invokeWrite(src, "royalty_default_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(bps, { type: "u32" }),
+  ]);
+export const royaltyRecipientSet = (src: string, id: number, recipient: string) =>
+  invokeWrite(src, "royalty_recipient_set", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+    Address.fromString(recipient).toScVal(),
+  ]);
+
+// ======================================
+// ============== Admin =================
+// ======================================
+export const readAdmin        = (src: string) => simulateCall(src, "admin");
+export const readPendingAdmin = (src: string) => simulateCall(src, "pending_admin"); // Option<Address>
+export const proposeAdmin = (src: string, newAdmin: string) =>
+  invokeWrite(src, "propose_admin", [Address.fromString(newAdmin).toScVal()]);
+export const acceptAdmin = (src: string) => invokeWrite(src, "accept_admin");
+
+/** Role enum trong contract (u32) */
+export const ROLES = { Minter: 0, FeeManager: 1, PaletteManager: 2, Pauser: 3 } as const;
+export type RoleName = keyof typeof ROLES;
+const roleScVal = (role: RoleName) => nativeToScVal(ROLES[role], { type: "u32" });
+
+export const readHasRole = (src: string, role: RoleName, account: string) =>
+  simulateCall(src, "has_role", [roleScVal(role), Address.fromString(account).toScVal()]);
+export const grantRole = (src: string, role: RoleName, account: string) =>
+  invokeWrite(src, "grant_role", [roleScVal(role), Address.fromString(account).toScVal()]);
+export const revokeRole = (src: string, role: RoleName, account: string) =>
+  invokeWrite(src, "revoke_role", [roleScVal(role), Address.fromString(account).toScVal()]);
+
+/** Feature enum trong contract (u32) */
+export const FEATURES = { All: 0, TokenTransfer: 1, NftTransfer: 2, Minting: 3, Market: 4 } as const;
+export type FeatureName = keyof typeof FEATURES;
+const featureScVal = (f: FeatureName) => nativeToScVal(FEATURES[f], { type: "u32" });
+
+export const readIsPaused = (src: string, feature: FeatureName) =>
+  simulateCall(src, "is_paused", [featureScVal(feature)]);
+export const pauseSet = (src: string, feature: FeatureName, paused: boolean) =>
+  invokeWrite(src, "pause_set", [
+    Address.fromString(src).toScVal(),
+    featureScVal(feature),
+    nativeToScVal(paused, { type: "bool" }),
+  ]);
+
+// ---- Council M-of-N ----
+export type AdminAction =
+  | { kind: "MintNft"; to: string; pixels: Uint8Array }
+  | { kind: "PaletteSet"; palette: number[] }
+  | { kind: "ListingFeeSet"; fee: bigint }
+  | { kind: "ProposeAdmin"; admin: string }
+  | { kind: "CouncilSet"; signers: string[]; threshold: number } // signers rỗng + 0 → tắt council
+  | { kind: "Upgrade"; wasmHash: Uint8Array }
+  | { kind: "TtlConfigSet"; config: TtlConfigInput }
+  | { kind: "GrantRole"; role: RoleName; account: string }
+  | { kind: "RevokeRole"; role: RoleName; account: string }
+  | { kind: "FtMint"; to: string; amount: bigint }
+  | { kind: "NftBurn"; id: number }
+  | { kind: "TimelockDelay"; delay: number } // tăng: ngay; giảm: xếp hàng timelock
+  | { kind: "TimelockCancel"; id: number }
+  | { kind: "TreasurySet"; treasury: string };
+
+/** enum AdminAction → ScVal dạng [Symbol(variant), ...fields] */
+function adminActionScVal(a: AdminAction) {
+  const tag = nativeToScVal(a.kind, { type: "symbol" });
+  switch (a.kind) {
+    case "MintNft":
+      assertPixels9x9(a.pixels);
+      return xdr.ScVal.scvVec([
+        tag,
+        Address.fromString(a.to).toScVal(),
+        nativeToScVal(a.pixels, { type: "bytes" }),
+      ]);
+    case "PaletteSet":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.palette, { type: "u32" })]);
+    case "ListingFeeSet":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.fee, { type: "i128" })]);
+    case "ProposeAdmin":
+      return xdr.ScVal.scvVec([tag, Address.fromString(a.admin).toScVal()]);
+    case "CouncilSet":
+      return xdr.ScVal.scvVec([
+        tag,
+        xdr.ScVal.scvVec(a.signers.map((s) => Address.fromString(s).toScVal())),
+        nativeToScVal(a.threshold, { type: "u32" }),
+      ]);
+    case "Upgrade":
+      if (a.wasmHash.length !== 32) throw new Error("WASM_HASH_32_BYTES");
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.wasmHash, { type: "bytes" })]);
+    case "TtlConfigSet":
+      return xdr.ScVal.scvVec([tag, ttlConfigScVal(a.config)]);
+    case "GrantRole":
+    case "RevokeRole":
+      return xdr.ScVal.scvVec([tag, roleScVal(a.role), Address.fromString(a.account).toScVal()]);
+    case "FtMint":
+      return xdr.ScVal.scvVec([
+        tag,
+        Address.fromString(a.to).toScVal(),
+        nativeToScVal(a.amount, { type: "i128" }),
+      ]);
+    case "NftBurn":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.id, { type: "u32" })]);
+    case "TimelockDelay":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.delay, { type: "u32" })]);
+    case "TimelockCancel":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(a.id, { type: "u32" })]);
+    case "TreasurySet":
+      return xdr.ScVal.scvVec([tag, Address.fromString(a.treasury).toScVal()]);
+  }
+}
+
+export const readCouncil = (src: string) => simulateCall(src, "council_get"); // Option<Council>
+export const readCouncilProposal = (src: string, id: number) =>
+  simulateCall(src, "council_proposal", [nativeToScVal(id, { type: "u32" })]);
+/** Chỉ dùng khi chưa có council; sau đó đổi qua councilPropose({ kind: "CouncilSet", ... }) */
+export const councilSet = (src: string, signers: string[], threshold: number) =>
+  invokeWrite(src, "council_set", [
+    xdr.ScVal.scvVec(signers.map((s) => Address.fromString(s).toScVal())),
+    nativeToScVal(threshold, { type: "u32" }),
+  ]);
+export const councilPropose = (src: string, action: AdminAction, expiresLedger: number) =>
+  invokeWrite(src, "council_propose", [
+    Address.fromString(src).toScVal(),
+    adminActionScVal(action),
+    nativeToScVal(expiresLedger, { type: "u32" }),
+  ]);
+export const councilApprove = (src: string, id: number) =>
+  invokeWrite(src, "council_approve", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+  ]);
+export const councilExecute = (src: string, id: number) =>
+  invokeWrite(src, "council_execute", [nativeToScVal(id, { type: "u32" })]);
+
+// ---- Timelock (LSTFEE, TFEE, SFEE, palette) ----
+export type TimelockChange =
+  | { kind: "ListingFee"; fee: bigint }
+  | { kind: "Palette"; palette: number[] }
+  | { kind: "Delay"; delay: number }
+  | { kind: "TransferFee"; config: TransferFeeInput }
+  | { kind: "SaleFee"; bps: number };
+
+function timelockChangeScVal(c: TimelockChange) {
+  const tag = nativeToScVal(c.kind, { type: "symbol" });
+  switch (c.kind) {
+    case "ListingFee":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(c.fee, { type: "i128" })]);
+    case "Palette":
+      if (c.palette.length !== 32) throw new Error("PALETTE_32_REQUIRED");
+      return xdr.ScVal.scvVec([tag, nativeToScVal(c.palette, { type: "u32" })]);
+    case "Delay":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(c.delay, { type: "u32" })]);
+    case "TransferFee":
+      return xdr.ScVal.scvVec([tag, transferFeeScVal(c.config)]);
+    case "SaleFee":
+      return xdr.ScVal.scvVec([tag, nativeToScVal(c.bps, { type: "u32" })]);
+  }
+}
+
+export const readTimelockDelay = (src: string) => simulateCall(src, "timelock_delay");
+/** Thay đổi đang chờ: [{ id, change, scheduled_by, eta_ledger }] */
+export const readTimelockPending = (src: string) => simulateCall(src, "timelock_pending");
+export const timelockDelaySet = (src: string, delay: number) =>
+  invokeWrite(src, "timelock_delay_set", [nativeToScVal(delay, { type: "u32" })]);
+export const timelockSchedule = (src: string, change: TimelockChange) =>
+  invokeWrite(src, "timelock_schedule", [
+    Address.fromString(src).toScVal(),
+    timelockChangeScVal(change),
+  ]);
+export const timelockCancel = (src: string, id: number) =>
+  invokeWrite(src, "timelock_cancel", [
+    Address.fromString(src).toScVal(),
+    nativeToScVal(id, { type: "u32" }),
+  ]);
+export const timelockExecute = (src: string, id: number) =>
+  invokeWrite(src, "timelock_execute", [nativeToScVal(id, { type: "u32" })]);
+
+// ======================================
+// ============ TTL / rent ==============
+// ======================================
+export const readTtlConfig = (src: string) => simulateCall(src, "ttl_config_get");
+export type TtlConfigInput = {
+  instanceThreshold: number;
+  instanceExtendTo: number;
+  persistentThreshold: number;
+  persistentExtendTo: number;
+};
+const ttlConfigScVal = (cfg: TtlConfigInput) =>
+  nativeToScVal(
+    {
+      instance_extend_to: cfg.instanceExtendTo,
+      instance_threshold: cfg.instanceThreshold,
+      persistent_extend_to: cfg.persistentExtendTo,
+      persistent_threshold: cfg.persistentThreshold,
+    },
+    {
+      type: {
+        instance_extend_to: ["symbol", "u32"],
+        instance_threshold: ["symbol", "u32"],
+        persistent_extend_to: ["symbol", "u32"],
+        persistent_threshold: ["symbol", "u32"],
+      },
+    }
+  );
+export const ttlConfigSet = (src: string, cfg: TtlConfigInput) =>
+  invokeWrite(src, "ttl_config_set", [ttlConfigScVal(cfg)]);
+export const bumpNft = (src: string, id: number) =>
+  invokeWrite(src, "bump_nft", [nativeToScVal(id, { type: "u32" })]);
+export const bumpAccount = (src: string, account: string) =>
+  invokeWrite(src, "bump_account", [Address.fromString(account).toScVal()]);
+
+/** Key persistent dạng (Symbol, arg) như trong contract */
+function persistentKey(tag: string, arg: xdr.ScVal) {
+  return xdr.LedgerKey.contractData(
+    new xdr.LedgerKeyContractData({
+      contract: new Address(CONTRACT_ID).toScAddress(),
+      key: xdr.ScVal.scvVec([nativeToScVal(tag, { type: "symbol" }), arg]),
+      durability: xdr.ContractDataDurability.persistent(),
+    })
+  );
+}
+
+/** Số ledger còn lại trước khi entry hết hạn (null nếu không tồn tại) */
+async function remainingTtl(key: xdr.LedgerKey): Promise<number | null> {
+  const res = await server.getLedgerEntries(key);
+  const entry = res.entries[0];
+  if (!entry?.liveUntilLedgerSeq) return null;
+  return entry.liveUntilLedgerSeq - res.latestLedger;
+}
+
+export const readNftTtl = (id: number) =>
+  remainingTtl(persistentKey("NFT", nativeToScVal(id, { type: "u32" })));
+export const readAccountTtl = async (account: string) => {
+  const addr = Address.fromString(account).toScVal();
+  const [balance, owned] = await Promise.all([
+    remainingTtl(persistentKey("BAL", addr)),
+    remainingTtl(persistentKey("OWN", addr)),
+  ]);
+  return { balance, owned };
+};
 
 // ======================================
 // =========== Tiện ích UI ==============